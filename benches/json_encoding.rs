@@ -140,7 +140,7 @@ fn criterion_benchmark(c: &mut Criterion) {
             self.0.to_json_buffer()
         }
         fn bytes_len(&self) -> usize {
-            self.0.as_bytes().len()
+            self.0.len()
         }
     }
     impl std::fmt::Debug for BenchStr {
@@ -1,4 +1,5 @@
-//! This module provides a procedural macro to derive JSONValue for custom structs.
+//! This module provides a procedural macro to derive JSONValue for custom structs,
+//! and [`flat_json_object!`](macro.flat_json_object.html), to create large objects.
 //!
 //! # Examples
 //! ```
@@ -25,19 +26,27 @@
 //! ```
 #![recursion_limit = "128"]
 extern crate proc_macro;
+extern crate proc_macro2;
 #[macro_use]
 extern crate quote;
 extern crate syn;
 
 use self::proc_macro::TokenStream;
+use proc_macro2::Delimiter;
 use syn::{
     Data,
     DeriveInput,
-    spanned::Spanned
+    buffer::Cursor,
+    parse_error,
+    spanned::Spanned,
+    synom::{PResult, Synom},
 };
 
 /// Derive JSONValue for a structure
-#[proc_macro_derive(JSONValue)]
+///
+/// Fields can be annotated with `#[json_in_type(as_string)]` to be serialized as strings,
+/// as with [`AsString`](https://docs.rs/json_in_type/latest/json_in_type/base_types/struct.AsString.html).
+#[proc_macro_derive(JSONValue, attributes(json_in_type))]
 pub fn jsonvalue_macro_derive(input: TokenStream) -> TokenStream {
     // Construct a representation of Rust code as a syntax tree
     // that we can manipulate
@@ -50,15 +59,15 @@ pub fn jsonvalue_macro_derive(input: TokenStream) -> TokenStream {
 fn impl_jsonvalue_macro(ast: &DeriveInput) -> TokenStream {
     let name = &ast.ident;
     match &ast.data {
-        Data::Struct(s) => impl_jsonvalue_macro_struct(&name, s),
-        Data::Enum(e) => impl_jsonvalue_macro_enum(&name, e),
+        Data::Struct(s) => impl_jsonvalue_macro_struct(name, s),
+        Data::Enum(e) => impl_jsonvalue_macro_enum(name, e),
         Data::Union(_) => unimplemented!()
     }
 }
 
 
 fn ident_to_litbytes(ident: &syn::Ident, first: bool) -> syn::LitByteStr {
-    let mut obj_key_str = format!("\"{}\":", ident);
+    let mut obj_key_str = format!("\"{}\":", ident_to_litstr(ident).value());
     obj_key_str.insert(0, if first { '{' } else { ',' });
     syn::LitByteStr::new(obj_key_str.as_bytes(), ident.span())
 }
@@ -67,10 +76,49 @@ fn field_to_litbytes(field: &syn::Field, first: bool) -> Option<syn::LitByteStr>
     field.clone().ident.map(|ident| ident_to_litbytes(&ident, first))
 }
 
+/// The json key of a field: its name, without the `r#` prefix of raw identifiers
+fn ident_to_litstr(ident: &syn::Ident) -> syn::LitStr {
+    let name = ident.to_string();
+    let name = name.trim_start_matches("r#");
+    syn::LitStr::new(name, ident.span())
+}
+
 fn field_to_ident(field: &syn::Field) -> Option<syn::Ident> {
     field.ident.clone()
 }
 
+/// Whether the field has the `#[json_in_type(as_string)]` attribute
+fn field_is_as_string(field: &syn::Field) -> bool {
+    field.attrs.iter()
+        .flat_map(|attr| attr.interpret_meta())
+        .flat_map(|meta| match meta {
+            syn::Meta::List(list) => if list.ident == "json_in_type" { list.nested.into_iter().collect() } else { vec![] },
+            _ => vec![],
+        })
+        .any(|nested| match nested {
+            syn::NestedMeta::Meta(syn::Meta::Word(ref word)) if word == "as_string" => true,
+            _ => panic!("unsupported json_in_type attribute, expected #[json_in_type(as_string)]"),
+        })
+}
+
+/// The expressions used to access the value of each field in the generated code
+fn field_values(fields: &syn::Fields) -> Vec<proc_macro2::TokenStream> {
+    fields.iter()
+        .enumerate()
+        .map(|(i, field)| {
+            let member = match field.ident.clone() {
+                Some(ident) => syn::Member::Named(ident),
+                None => syn::Member::Unnamed(syn::Index { index: i as u32, span: field.span() }),
+            };
+            if field_is_as_string(field) {
+                quote! { ::json_in_type::base_types::AsString(&self.#member) }
+            } else {
+                quote! { self.#member }
+            }
+        })
+        .collect()
+}
+
 fn write_named_fields(
     fields_named: &syn::FieldsNamed,
 ) -> impl quote::ToTokens {
@@ -79,16 +127,42 @@ fn write_named_fields(
         .enumerate()
         .flat_map(|(i, f)| field_to_litbytes(f, i == 0))
         .collect();
+    let values = field_values(&syn::Fields::Named(fields_named.clone()));
+    let end = syn::LitByteStr::new(if values.is_empty() { b"{}" } else { b"}" }, fs.span());
+    quote! {
+        #(
+            w.write_all(#names)?;
+            #values.write_json(w)?;
+        )*
+        w.write_all(#end)
+    }
+}
+
+fn write_named_fields_formatted(
+    fields_named: &syn::FieldsNamed,
+) -> impl quote::ToTokens {
+    let fs = fields_named.named.clone();
     let fields: Vec<syn::Ident> = fs.iter()
         .flat_map(field_to_ident)
         .collect();
-    let end = syn::LitByteStr::new(if fields.is_empty() { b"{}" } else { b"}" }, fs.span());
+    let keys: Vec<syn::LitStr> = fields.iter()
+        .map(ident_to_litstr)
+        .collect();
+    let firsts: Vec<bool> = (0..fields.len()).map(|i| i == 0).collect();
+    let empty = fields.is_empty();
+    let values = field_values(&syn::Fields::Named(fields_named.clone()));
     quote! {
+        if f.sorts_keys() {
+            return ::json_in_type::object::write_sorted_entries(self, w, f);
+        }
+        f.begin_object(w)?;
         #(
-            w.write_all(#names)?;
-            self.#fields.write_json(w)?;
+            f.begin_object_key(w, #firsts)?;
+            f.write_string(w, #keys)?;
+            f.begin_object_value(w)?;
+            #values.write_json_formatted(w, f)?;
         )*
-        w.write_all(#end)
+        f.end_object(w, #empty)
     }
 }
 
@@ -100,19 +174,101 @@ fn write_unnamed_fields(
     let commas: Vec<syn::LitByteStr> = nums.iter()
         .map(|i| syn::LitByteStr::new(if *i == 0 { b"[" } else { b"," }, fs.span()))
         .collect();
-    let members: Vec<syn::Member> = nums.iter()
-        .map(|i| syn::Member::Unnamed(syn::Index { index: *i, span: fs.span() }))
-        .collect();
+    let values = field_values(&syn::Fields::Unnamed(fields_named.clone()));
     let end = syn::LitByteStr::new(if commas.is_empty() { b"[]" } else { b"]" }, fs.span());
     quote! {
         #(
             w.write_all(#commas)?;
-            self.#members.write_json(w)?;
+            #values.write_json(w)?;
         )*
         w.write_all(#end)
     }
 }
 
+fn write_unnamed_fields_formatted(
+    fields_named: &syn::FieldsUnnamed,
+) -> impl quote::ToTokens {
+    let fs = fields_named.unnamed.clone();
+    let nums: Vec<u32> = fs.iter().enumerate().map(|(i, _)| i as u32).collect();
+    let firsts: Vec<bool> = nums.iter().map(|i| *i == 0).collect();
+    let values = field_values(&syn::Fields::Unnamed(fields_named.clone()));
+    let empty = nums.is_empty();
+    quote! {
+        f.begin_array(w)?;
+        #(
+            f.begin_array_value(w, #firsts)?;
+            #values.write_json_formatted(w, f)?;
+        )*
+        f.end_array(w, #empty)
+    }
+}
+
+fn write_fields_formatted(
+    fields: &syn::Fields,
+) -> Box<dyn quote::ToTokens> {
+    match fields {
+        syn::Fields::Named(fields_named) =>
+            Box::new(write_named_fields_formatted(fields_named)),
+        syn::Fields::Unnamed(fields_unnamed) =>
+            Box::new(write_unnamed_fields_formatted(fields_unnamed)),
+        syn::Fields::Unit =>
+            Box::new(quote! {w.write_all(b"null")})
+    }
+}
+
+fn fields_json_len(
+    fields: &syn::Fields,
+) -> Box<dyn quote::ToTokens> {
+    let constant_len: usize = match fields {
+        syn::Fields::Named(fields_named) => {
+            let fs = &fields_named.named;
+            let keys_len: usize = fs.iter()
+                .enumerate()
+                .flat_map(|(i, f)| field_to_litbytes(f, i == 0))
+                .map(|key| key.value().len())
+                .sum();
+            let end_len = if fs.is_empty() { 2 } else { 1 };
+            keys_len + end_len
+        }
+        syn::Fields::Unnamed(fields_unnamed) => {
+            let fs = &fields_unnamed.unnamed;
+            // One bracket or comma before each field, and a closing bracket
+            fs.len() + if fs.is_empty() { 2 } else { 1 }
+        }
+        syn::Fields::Unit => 4,
+    };
+    let values = field_values(fields);
+    Box::new(quote! {
+        Some(#constant_len #( + #values.json_len()? )*)
+    })
+}
+
+/// Lets the entries of an object be visited, to sort them
+fn impl_object_entries(
+    name: &syn::Ident,
+    fields: &syn::Fields,
+) -> Box<dyn quote::ToTokens> {
+    let fields_named = match fields {
+        syn::Fields::Named(fields_named) => fields_named,
+        _ => return Box::new(quote! {}),
+    };
+    let keys: Vec<syn::LitStr> = fields_named.named.iter()
+        .flat_map(field_to_ident)
+        .map(|ident| ident_to_litstr(&ident))
+        .collect();
+    let values = field_values(fields);
+    Box::new(quote! {
+        impl ::json_in_type::object::ObjectEntries for #name {
+            fn visit_entries<E: ::json_in_type::object::EntryVisitor>(&self, visitor: &mut E) -> Result<(), E::Error> {
+                #(
+                    visitor.visit_entry(&#keys, &#values)?;
+                )*
+                Ok(())
+            }
+        }
+    })
+}
+
 fn write_fields(
     fields: &syn::Fields,
 ) -> Box<dyn quote::ToTokens> {
@@ -131,11 +287,28 @@ fn impl_jsonvalue_macro_struct(
     struct_data: &syn::DataStruct,
 ) -> TokenStream {
     let write_fields_ts = write_fields(&struct_data.fields);
+    let write_fields_formatted_ts = write_fields_formatted(&struct_data.fields);
+    let fields_json_len_ts = fields_json_len(&struct_data.fields);
+    let object_entries_ts = impl_object_entries(name, &struct_data.fields);
     (quote! {
+        #object_entries_ts
+
         impl JSONValue for #name {
-            fn write_json<W: std::io::Write>(&self, w: &mut W) -> std::io::Result<()> {
+            fn write_json<W: ::json_in_type::write::JSONWrite>(&self, w: &mut W) -> Result<(), W::Error> {
                 #write_fields_ts
             }
+
+            fn write_json_formatted<W: ::json_in_type::write::JSONWrite>(
+                &self,
+                w: &mut W,
+                f: &mut ::json_in_type::format::JSONFormatter,
+            ) -> Result<(), W::Error> {
+                #write_fields_formatted_ts
+            }
+
+            fn json_len(&self) -> Option<usize> {
+                #fields_json_len_ts
+            }
         }
     }).into()
 }
@@ -149,7 +322,17 @@ fn impl_jsonvalue_macro_enum(
     let variants_json: Vec<_> = idents.clone().iter()
         .map(|ident| ident_to_litbytes(ident, true))
         .collect();
+    let variants_keys: Vec<_> = idents.iter()
+        .map(ident_to_litstr)
+        .collect();
     let names = std::iter::repeat(name);
+    let variants_len: Vec<usize> = variants_json.iter()
+        .map(|key| key.value().len() + b"true}".len())
+        .collect();
+    let names_formatted = std::iter::repeat(name);
+    let names_len = std::iter::repeat(name);
+    let idents_len = idents.clone();
+    let idents_formatted = idents.clone();
     for v in vs.iter() {
         match v.fields {
             syn::Fields::Unnamed(_) => unimplemented!(),
@@ -159,7 +342,7 @@ fn impl_jsonvalue_macro_enum(
     }
     (quote! {
         impl JSONValue for #name {
-            fn write_json<W: std::io::Write>(&self, w: &mut W) -> std::io::Result<()> {
+            fn write_json<W: ::json_in_type::write::JSONWrite>(&self, w: &mut W) -> Result<(), W::Error> {
                 match self {
                 #(
                     #names::#idents => {
@@ -169,6 +352,322 @@ fn impl_jsonvalue_macro_enum(
                 ),*
                 }
             }
+
+            fn write_json_formatted<W: ::json_in_type::write::JSONWrite>(
+                &self,
+                w: &mut W,
+                f: &mut ::json_in_type::format::JSONFormatter,
+            ) -> Result<(), W::Error> {
+                f.begin_object(w)?;
+                f.begin_object_key(w, true)?;
+                f.write_string(w, match self {
+                #(
+                    #names_formatted::#idents_formatted => #variants_keys
+                ),*
+                })?;
+                f.begin_object_value(w)?;
+                w.write_all(b"true")?;
+                f.end_object(w, false)
+            }
+
+            fn json_len(&self) -> Option<usize> {
+                Some(match self {
+                #(
+                    #names_len::#idents_len => #variants_len
+                ),*
+                })
+            }
         }
     }).into()
-}
\ No newline at end of file
+}
+
+/// Creates a json object with the syntax of
+/// [`json_object!`](https://docs.rs/json_in_type/latest/json_in_type/macro.json_object.html),
+/// as a single struct with one field per member.
+///
+/// `json_object!` nests one type per member, which makes objects with hundreds of members
+/// slow to compile, and can exceed the recursion limit. `flat_json_object!` generates a single
+/// struct per invocation, with a flat serialization function, that serializes just as fast,
+/// and has the same size in memory.
+///
+/// Keys can be identifiers, string literals, or expressions in square brackets,
+/// and values can be omitted when they are variables with the same name as the key.
+/// Optional keys, conditional keys and spreads are not supported.
+///
+/// `null`, `true` and `false` are values, not variables, so they cannot be used without a value:
+/// ```compile_fail
+/// # #[macro_use] extern crate json_in_type_derive;
+/// let obj = flat_json_object! { null, ok: true };
+/// ```
+/// A key with one of these names is written with its value, as in `null: null`.
+///
+/// # Examples
+/// ```
+/// extern crate json_in_type;
+/// #[macro_use] extern crate json_in_type_derive;
+/// use json_in_type::JSONValue;
+///
+/// let id = 42;
+/// let name = "status";
+/// let obj = flat_json_object! {
+///     id,
+///     "content-type": "application/json",
+///     r#type: null,
+///     [name]: true,
+/// };
+/// assert_eq!(
+///     r#"{"id":42,"content-type":"application/json","type":null,"status":true}"#,
+///     obj.to_json_string()
+/// );
+/// ```
+#[proc_macro]
+pub fn flat_json_object(input: TokenStream) -> TokenStream {
+    match syn::parse::<FlatObject>(input) {
+        Ok(object) => match object.members.iter().find_map(FlatMember::literal_shorthand) {
+            Some(literal) => {
+                let message = format!(
+                    "`{0}` is not a variable, and needs a key: write `\"{0}\": {0}`",
+                    literal
+                );
+                (quote_spanned! { literal.span()=> compile_error!(#message) }).into()
+            }
+            None => impl_flat_object(&object),
+        },
+        Err(err) => {
+            let message = format!("invalid flat_json_object! syntax: {}", err);
+            (quote! { compile_error!(#message) }).into()
+        }
+    }
+}
+
+/// The key of a member of a flat object
+enum FlatKey {
+    /// Known at compile time
+    Name(syn::LitStr),
+    /// Computed at runtime
+    Computed(syn::Expr),
+}
+
+struct FlatMember {
+    key: FlatKey,
+    value: syn::Expr,
+    /// Whether the value was omitted, and is the variable named after the key
+    shorthand: bool,
+}
+
+impl FlatMember {
+    /// `null`, `true` or `false` written alone, that would otherwise become a key
+    fn literal_shorthand(&self) -> Option<&syn::Ident> {
+        match &self.value {
+            syn::Expr::Path(syn::ExprPath { path, .. }) if self.shorthand => path
+                .segments
+                .first()
+                .map(|segment| &segment.value().ident)
+                .filter(|ident| ident == &"null" || ident == &"true" || ident == &"false"),
+            _ => None,
+        }
+    }
+}
+
+struct FlatObject {
+    members: Vec<FlatMember>,
+}
+
+impl Synom for FlatMember {
+    fn parse(input: Cursor) -> PResult<Self> {
+        let (key, shorthand, rest) = if let Some((ident, rest)) = input.ident() {
+            (FlatKey::Name(ident_to_litstr(&ident)), Some(ident), rest)
+        } else if let Some((inside, _span, rest)) = input.group(Delimiter::Bracket) {
+            let (expr, after) = <syn::Expr as Synom>::parse(inside)?;
+            if !after.eof() {
+                return parse_error();
+            }
+            (FlatKey::Computed(expr), None, rest)
+        } else {
+            let (lit, rest) = <syn::LitStr as Synom>::parse(input)?;
+            (FlatKey::Name(lit), None, rest)
+        };
+        match <syn::token::Colon as Synom>::parse(rest) {
+            Ok((_colon, rest)) => {
+                let (value, rest) = <syn::Expr as Synom>::parse(rest)?;
+                Ok((FlatMember { key, value, shorthand: false }, rest))
+            }
+            Err(err) => match shorthand {
+                Some(ident) => {
+                    let value = syn::Expr::Path(syn::ExprPath {
+                        attrs: vec![],
+                        qself: None,
+                        path: ident.into(),
+                    });
+                    Ok((FlatMember { key, value, shorthand: true }, rest))
+                }
+                None => Err(err),
+            },
+        }
+    }
+}
+
+impl Synom for FlatObject {
+    fn parse(mut input: Cursor) -> PResult<Self> {
+        let mut members = vec![];
+        while !input.eof() {
+            let (member, rest) = <FlatMember as Synom>::parse(input)?;
+            members.push(member);
+            input = rest;
+            if !input.eof() {
+                input = <syn::token::Comma as Synom>::parse(input)?.1;
+            }
+        }
+        Ok((FlatObject { members }, input))
+    }
+}
+
+/// The json representation of a string, with the same escaping as json_in_type
+fn json_string(s: &str) -> String {
+    let mut json = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\x08' => json.push_str("\\b"),
+            '\t' => json.push_str("\\t"),
+            '\n' => json.push_str("\\n"),
+            '\x0c' => json.push_str("\\f"),
+            '\r' => json.push_str("\\r"),
+            '\x00'..='\x1f' | '\x7f' => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+/// `null`, `true` and `false` are written as zero-sized values
+fn flat_value(value: &syn::Expr) -> proc_macro2::TokenStream {
+    match value {
+        syn::Expr::Path(syn::ExprPath { qself: None, path, .. })
+            if path.segments.len() == 1 && path.segments[0].ident == "null" =>
+            quote! { () },
+        syn::Expr::Lit(syn::ExprLit { lit: syn::Lit::Bool(b), .. }) =>
+            if b.value {
+                quote! { ::json_in_type::base_types::JSONtrue }
+            } else {
+                quote! { ::json_in_type::base_types::JSONfalse }
+            },
+        _ => quote! { #value },
+    }
+}
+
+fn impl_flat_object(object: &FlatObject) -> TokenStream {
+    let span = proc_macro2::Span::call_site();
+    let ident = |name: String| syn::Ident::new(&name, span);
+    let mut params = vec![];
+    let mut bounds = vec![];
+    let mut fields = vec![];
+    let mut field_types = vec![];
+    let mut exprs = vec![];
+    let mut writes = vec![];
+    let mut writes_formatted = vec![];
+    let mut lens = vec![];
+    let mut visits = vec![];
+    let mut constant_len = if object.members.is_empty() { 2 } else { 1 };
+    for (i, member) in object.members.iter().enumerate() {
+        let first = i == 0;
+        let start = if first { "{" } else { "," };
+        let value_type = ident(format!("V{}", i));
+        let value_field = ident(format!("value{}", i));
+        let value = flat_value(&member.value);
+        params.push(value_type.clone());
+        bounds.push(quote! { #value_type: ::json_in_type::JSONValue });
+        fields.push(value_field.clone());
+        field_types.push(value_type);
+        exprs.push(value);
+        match &member.key {
+            FlatKey::Name(name) => {
+                let prefix = format!("{}{}:", start, json_string(&name.value()));
+                constant_len += prefix.len();
+                let prefix = syn::LitByteStr::new(prefix.as_bytes(), span);
+                writes.push(quote! { w.write_all(#prefix)?; });
+                writes_formatted.push(quote! { f.write_string(w, #name)?; });
+                visits.push(quote! { visitor.visit_entry(&#name, &self.#value_field)?; });
+            }
+            FlatKey::Computed(key) => {
+                let key_type = ident(format!("K{}", i));
+                let key_field = ident(format!("key{}", i));
+                constant_len += 2;
+                let start = syn::LitByteStr::new(start.as_bytes(), span);
+                writes.push(quote! {
+                    w.write_all(#start)?;
+                    self.#key_field.write_json(w)?;
+                    w.write_all(b":")?;
+                });
+                writes_formatted.push(quote! { self.#key_field.write_json_formatted(w, f)?; });
+                lens.push(quote! { self.#key_field.json_len()? });
+                visits.push(quote! { visitor.visit_entry(&self.#key_field, &self.#value_field)?; });
+                params.push(key_type.clone());
+                bounds.push(quote! { #key_type: ::json_in_type::string::JSONString });
+                fields.push(key_field);
+                field_types.push(key_type);
+                exprs.push(quote! { #key });
+            }
+        }
+        lens.push(quote! { self.#value_field.json_len()? });
+    }
+    let values: Vec<syn::Ident> = (0..object.members.len())
+        .map(|i| ident(format!("value{}", i)))
+        .collect();
+    let values_formatted = values.clone();
+    let firsts: Vec<bool> = (0..object.members.len()).map(|i| i == 0).collect();
+    let end = syn::LitByteStr::new(if values.is_empty() { b"{}" } else { b"}" }, span);
+    let empty = values.is_empty();
+    let (params, bounds, fields) = (&params, &bounds, &fields);
+    (quote! {{
+        #[derive(Clone, Copy, Debug)]
+        struct FlatJSONObject<#(#params),*> {
+            #( #fields: #field_types ),*
+        }
+
+        impl<#(#bounds),*> ::json_in_type::JSONValue for FlatJSONObject<#(#params),*> {
+            fn write_json<W: ::json_in_type::write::JSONWrite>(&self, w: &mut W) -> Result<(), W::Error> {
+                #(
+                    #writes
+                    self.#values.write_json(w)?;
+                )*
+                w.write_all(#end)
+            }
+
+            fn write_json_formatted<W: ::json_in_type::write::JSONWrite>(
+                &self,
+                w: &mut W,
+                f: &mut ::json_in_type::format::JSONFormatter,
+            ) -> Result<(), W::Error> {
+                if f.sorts_keys() {
+                    return ::json_in_type::object::write_sorted_entries(self, w, f);
+                }
+                f.begin_object(w)?;
+                #(
+                    f.begin_object_key(w, #firsts)?;
+                    #writes_formatted
+                    f.begin_object_value(w)?;
+                    self.#values_formatted.write_json_formatted(w, f)?;
+                )*
+                f.end_object(w, #empty)
+            }
+
+            fn json_len(&self) -> Option<usize> {
+                Some(#constant_len #( + #lens )*)
+            }
+        }
+
+        impl<#(#bounds),*> ::json_in_type::object::ObjectEntries for FlatJSONObject<#(#params),*> {
+            fn visit_entries<E: ::json_in_type::object::EntryVisitor>(&self, visitor: &mut E) -> Result<(), E::Error> {
+                #( #visits )*
+                Ok(())
+            }
+        }
+
+        FlatJSONObject {
+            #( #fields: #exprs ),*
+        }
+    }}).into()
+}
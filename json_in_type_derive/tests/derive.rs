@@ -44,3 +44,157 @@ fn test_enum() {
     let obj = Val::C;
     assert_eq!(r#"{"C":true}"#, obj.to_json_string());
 }
+
+#[allow(dead_code)]
+#[derive(JSONValue)]
+struct Nested {
+    inner: MyObject,
+    pair: WrapperStruct,
+    val: Val,
+}
+
+#[test]
+fn test_pretty() {
+    let obj = Nested {
+        inner: MyObject {
+            void: (),
+            list: vec![],
+            hello: String::from("world"),
+        },
+        pair: WrapperStruct(9, 4),
+        val: Val::A,
+    };
+    assert_eq!(
+        r#"{
+  "inner": {
+    "void": null,
+    "list": [],
+    "hello": "world"
+  },
+  "pair": [
+    9,
+    4
+  ],
+  "val": {
+    "A": true
+  }
+}"#,
+        obj.to_json_string_pretty()
+    );
+}
+
+#[allow(dead_code)]
+#[derive(JSONValue)]
+struct Unit;
+
+#[allow(dead_code)]
+#[derive(JSONValue)]
+struct Empty {}
+
+#[test]
+fn test_len() {
+    fn check<T: JSONValue>(value: T) {
+        assert_eq!(Some(value.to_json_string().len()), value.json_len());
+    }
+    check(MyObject {
+        void: (),
+        list: vec![1, 22, 3],
+        hello: String::from("world"),
+    });
+    check(WrapperStruct(9, 40));
+    check(Val::A);
+    check(Val::C);
+    check(Unit);
+    check(Empty {});
+}
+
+#[allow(dead_code)]
+#[derive(JSONValue)]
+struct Measure {
+    value: f64,
+    previous: Option<f32>,
+}
+
+#[test]
+fn test_non_finite_floats() {
+    use json_in_type::format::{JSONFormatter, NonFiniteFloats};
+    let obj = Measure {
+        value: 1.5,
+        previous: Some(f32::NAN),
+    };
+    let mut buffer = vec![];
+    let mut formatter = JSONFormatter::compact().non_finite_floats(NonFiniteFloats::String);
+    obj.write_json_formatted(&mut buffer, &mut formatter).unwrap();
+    assert_eq!(br#"{"value":1.5,"previous":"NaN"}"#.to_vec(), buffer);
+    let mut formatter = JSONFormatter::compact().non_finite_floats(NonFiniteFloats::Error);
+    assert!(obj.write_json_formatted(&mut vec![], &mut formatter).is_err());
+}
+
+#[derive(JSONValue)]
+struct Account {
+    #[json_in_type(as_string)]
+    id: u64,
+    balance: i32,
+}
+
+#[derive(JSONValue)]
+struct AccountId(#[json_in_type(as_string)] u64);
+
+#[test]
+fn test_as_string() {
+    let account = Account { id: 9_007_199_254_740_993, balance: -3 };
+    assert_eq!(r#"{"id":"9007199254740993","balance":-3}"#, account.to_json_string());
+    assert_eq!(Some(account.to_json_string().len()), account.json_len());
+    assert_eq!(r#"["42"]"#, AccountId(42).to_json_string());
+}
+
+#[derive(JSONValue)]
+struct Unsorted {
+    zebra: u8,
+    #[json_in_type(as_string)]
+    apple: u64,
+    mango: Account,
+}
+
+#[test]
+fn test_canonical() {
+    use json_in_type::format::JSONFormatter;
+    let obj = Unsorted {
+        zebra: 1,
+        apple: 2,
+        mango: Account { id: 3, balance: 4 },
+    };
+    let mut buffer = vec![];
+    obj.write_json_formatted(&mut buffer, &mut JSONFormatter::canonical()).unwrap();
+    assert_eq!(
+        br#"{"apple":"2","mango":{"balance":4,"id":"3"},"zebra":1}"#.to_vec(),
+        buffer
+    );
+}
+
+#[test]
+fn test_spread() {
+    use json_in_type::*;
+    let account = Account { id: 1, balance: 2 };
+    let obj = json_object! { kind: "account", ..&account };
+    assert_eq!(r#"{"kind":"account","id":"1","balance":2}"#, obj.to_json_string());
+    assert_eq!(Some(obj.to_json_string().len()), obj.json_len());
+}
+
+#[derive(JSONValue)]
+struct Link {
+    r#type: &'static str,
+    href: &'static str,
+}
+
+#[test]
+fn test_raw_identifiers() {
+    let link = Link { r#type: "text/html", href: "/" };
+    let expected = r#"{"type":"text/html","href":"/"}"#;
+    assert_eq!(expected, link.to_json_string());
+    assert_eq!(Some(expected.len()), link.json_len());
+    assert_eq!(
+        "{\n  \"type\": \"text/html\",\n  \"href\": \"/\"\n}",
+        link.to_json_string_pretty()
+    );
+}
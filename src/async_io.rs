@@ -0,0 +1,609 @@
+//! Asynchronous serialization to a tokio
+//! [`AsyncWrite`](https://docs.rs/tokio/1/tokio/io/trait.AsyncWrite.html).
+//!
+//! This module is only available with the `tokio` feature.
+//!
+//! Values are serialized to an intermediate buffer, that is handed off to the
+//! asynchronous writer whenever it reaches a fixed capacity.
+//! Serialization itself is synchronous, so it cannot wait for the writer
+//! in the middle of a value: once the writer is not ready to accept more bytes,
+//! the rest of the value is kept in the buffer, and sent once the value is serialized.
+//! The buffer is thus **not** bounded while a value is serialized with
+//! [`write_value`](struct.AsyncJSONWriter.html#method.write_value):
+//! it holds as much of the value as the writer did not accept.
+//!
+//! Memory use is bounded by the capacity of the buffer in two cases:
+//!
+//!  - a [`Stream`](https://docs.rs/futures-core/0.3/futures_core/stream/trait.Stream.html)
+//!    of values is serialized as a JSON list, one value at a time,
+//!    waiting for the writer between values,
+//!    so at most one value is buffered beyond the capacity;
+//!  - [`write_value_chunked`](struct.AsyncJSONWriter.html#method.write_value_chunked)
+//!    serializes a single value on tokio's blocking thread pool,
+//!    and waits for the writer after every chunk.
+//!
+//! # Errors
+//!
+//! A value that fails to serialize is not written entirely,
+//! but the bytes that were sent before the failure stay in the output,
+//! which is then not valid JSON.
+//! The same goes for a stream of values, whose list is left unterminated.
+//! The output cannot be repaired, so it should be discarded,
+//! for instance by closing the connection instead of ending the response normally.
+//!
+//! # Examples
+//!
+//! ```
+//! use json_in_type::*;
+//! use json_in_type::async_io::{write_json_async, write_json_stream};
+//! # futures::executor::block_on(async {
+//!
+//! let mut response: Vec<u8> = vec![];
+//! write_json_async(&json_object! { ok: true }, &mut response).await?;
+//! assert_eq!(br#"{"ok":true}"#.to_vec(), response);
+//!
+//! let mut response: Vec<u8> = vec![];
+//! let values = futures::stream::iter((1..3).map(|id| json_object! { id }));
+//! write_json_stream(values, &mut response).await?;
+//! assert_eq!(br#"[{"id":1},{"id":2}]"#.to_vec(), response);
+//! # Ok::<(), std::io::Error>(()) }).unwrap();
+//! ```
+
+use super::format::JSONFormatter;
+use super::JSONValue;
+use core::future::poll_fn;
+use core::pin::{pin, Pin};
+use core::task::{Context, Poll};
+use futures_core::Stream;
+use std::io;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use tokio::sync::mpsc;
+use tokio::task;
+
+/// The default capacity of the intermediate buffer, in bytes
+pub const DEFAULT_CAPACITY: usize = 8 * 1024;
+
+/// Serializes JSON values to an asynchronous writer.
+pub struct AsyncJSONWriter<W: AsyncWrite + Unpin> {
+    writer: W,
+    buffer: Vec<u8>,
+    capacity: usize,
+}
+
+impl<W: AsyncWrite + Unpin> AsyncJSONWriter<W> {
+    /// Creates a writer with an intermediate buffer of
+    /// [`DEFAULT_CAPACITY`](constant.DEFAULT_CAPACITY.html) bytes
+    pub fn new(writer: W) -> AsyncJSONWriter<W> {
+        AsyncJSONWriter::with_capacity(DEFAULT_CAPACITY, writer)
+    }
+
+    /// Creates a writer that sends its intermediate buffer to the underlying writer
+    /// as soon as it holds more than `capacity` bytes
+    pub fn with_capacity(capacity: usize, writer: W) -> AsyncJSONWriter<W> {
+        AsyncJSONWriter {
+            writer,
+            buffer: Vec::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    async fn write_buffer_if_full(&mut self) -> io::Result<()> {
+        if self.buffer.len() >= self.capacity {
+            self.writer.write_all(&self.buffer).await?;
+            self.buffer.clear();
+        }
+        Ok(())
+    }
+
+    /// Serializes `write` to the buffer, handing the buffer off to the writer
+    /// whenever it is full, until the writer is not ready.
+    /// If serialization fails, the part of the value that is still buffered is discarded.
+    async fn write_with<F>(&mut self, mut write: F) -> io::Result<()>
+    where
+        F: FnMut(&mut HandOff<W>) -> io::Result<()>,
+    {
+        let start = self.buffer.len();
+        let result = poll_fn(|cx| {
+            let mut hand_off = HandOff {
+                writer: &mut self.writer,
+                buffer: &mut self.buffer,
+                start,
+                capacity: self.capacity,
+                pending: false,
+                cx,
+            };
+            let result = write(&mut hand_off);
+            if result.is_err() {
+                let start = hand_off.start;
+                self.buffer.truncate(start);
+            }
+            Poll::Ready(result)
+        })
+        .await;
+        result?;
+        self.write_buffer_if_full().await
+    }
+
+    /// Serializes a single value.
+    /// When the writer is not ready, the rest of the value is buffered
+    /// until it is entirely serialized, whatever its size.
+    ///
+    /// If the value cannot be serialized, the error is returned,
+    /// and the part of the value that has not been sent yet is discarded,
+    /// but the part that has been sent is not: see [errors](index.html#errors).
+    pub async fn write_value<T: JSONValue + ?Sized>(&mut self, value: &T) -> io::Result<()> {
+        self.write_with(|w| value.write_json(w)).await
+    }
+
+    /// Serializes a single value using the given formatter
+    pub async fn write_value_formatted<T: JSONValue + ?Sized>(
+        &mut self,
+        value: &T,
+        f: &mut JSONFormatter,
+    ) -> io::Result<()> {
+        self.write_with(|w| value.write_json_formatted(w, f)).await
+    }
+
+    /// Serializes a single value on the blocking thread pool of the tokio runtime,
+    /// by chunks of the capacity of the buffer.
+    /// The serialization waits for each chunk to be sent before producing the next one,
+    /// so memory use is bounded even for very large values, such as
+    /// iterators wrapped in a `RefCell`, and writers that are slow to accept data.
+    ///
+    /// Handing the value off to another thread is much more expensive than serializing
+    /// a small value, and the value has to be moved there, so it cannot borrow local data.
+    ///
+    /// # Errors
+    ///
+    /// If the serialization fails or panics, the error is returned once the chunks
+    /// that were serialized before have been sent, so the output is left incomplete:
+    /// see [errors](index.html#errors).
+    ///
+    /// # Panics
+    ///
+    /// When called outside of a tokio runtime.
+    pub async fn write_value_chunked<T>(&mut self, value: T) -> io::Result<()>
+    where
+        T: JSONValue + Send + 'static,
+    {
+        self.writer.write_all(&self.buffer).await?;
+        self.buffer.clear();
+        let (sender, mut chunks) = mpsc::channel(1);
+        let capacity = self.capacity;
+        let serialization = task::spawn_blocking(move || {
+            let mut writer = ChunkSender {
+                sender,
+                chunk: Vec::with_capacity(capacity),
+                capacity,
+            };
+            value.write_json(&mut writer)?;
+            writer.send_chunk()
+        });
+        while let Some(chunk) = chunks.recv().await {
+            self.writer.write_all(&chunk).await?;
+        }
+        // The chunks end when the serialization stops, successfully or not
+        serialization.await?
+    }
+
+    /// Serializes all the values of a stream as a JSON list,
+    /// as they are produced by the stream.
+    ///
+    /// If a value cannot be serialized, the error is returned and the list is left
+    /// unterminated: see [errors](index.html#errors).
+    /// The separator that precedes the failed value is discarded along with it,
+    /// unless it has already been sent.
+    pub async fn write_stream<T, S>(&mut self, stream: S) -> io::Result<()>
+    where
+        T: JSONValue,
+        S: Stream<Item = T>,
+    {
+        let mut stream = pin!(stream);
+        let mut empty = true;
+        self.buffer.push(b'[');
+        while let Some(value) = poll_fn(|cx| stream.as_mut().poll_next(cx)).await {
+            let separator: &[u8] = if empty { b"" } else { b"," };
+            self.write_with(|w| {
+                io::Write::write_all(w, separator)?;
+                value.write_json(w)
+            })
+            .await?;
+            empty = false;
+        }
+        self.buffer.push(b']');
+        self.write_buffer_if_full().await
+    }
+
+    /// Serializes all the values of a stream as a JSON list, using the given formatter.
+    /// Errors are handled as in [`write_stream`](#method.write_stream).
+    pub async fn write_stream_formatted<T, S>(
+        &mut self,
+        stream: S,
+        f: &mut JSONFormatter,
+    ) -> io::Result<()>
+    where
+        T: JSONValue,
+        S: Stream<Item = T>,
+    {
+        let mut stream = pin!(stream);
+        let mut empty = true;
+        f.begin_array(&mut self.buffer)?;
+        while let Some(value) = poll_fn(|cx| stream.as_mut().poll_next(cx)).await {
+            self.write_with(|w| {
+                f.begin_array_value(w, empty)?;
+                value.write_json_formatted(w, f)
+            })
+            .await?;
+            empty = false;
+        }
+        f.end_array(&mut self.buffer, empty)?;
+        self.write_buffer_if_full().await
+    }
+
+    /// Sends the contents of the intermediate buffer, and flushes the underlying writer
+    pub async fn flush(&mut self) -> io::Result<()> {
+        self.writer.write_all(&self.buffer).await?;
+        self.buffer.clear();
+        self.writer.flush().await
+    }
+
+    /// Returns the underlying writer.
+    /// [`flush`](#method.flush) has to be called before, or buffered data will be lost.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+/// Hands the buffer off to an asynchronous writer whenever it is full,
+/// while a value is being serialized
+struct HandOff<'a, 'b, W: AsyncWrite + Unpin> {
+    writer: &'a mut W,
+    buffer: &'a mut Vec<u8>,
+    /// The position in the buffer where the current value starts
+    start: usize,
+    capacity: usize,
+    /// Whether the writer was not ready, and will wake the task up when it is
+    pending: bool,
+    cx: &'a mut Context<'b>,
+}
+
+impl<W: AsyncWrite + Unpin> io::Write for HandOff<'_, '_, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buffer.extend_from_slice(buf);
+        while !self.pending && self.buffer.len() >= self.capacity {
+            match Pin::new(&mut *self.writer).poll_write(self.cx, self.buffer) {
+                Poll::Ready(Ok(0)) => return Err(io::ErrorKind::WriteZero.into()),
+                Poll::Ready(Ok(size)) => {
+                    self.buffer.drain(..size);
+                    self.start = self.start.saturating_sub(size);
+                }
+                Poll::Ready(Err(err)) => return Err(err),
+                // The serialization cannot be suspended until the writer is ready,
+                // so the rest of the value is buffered, and the writer is not polled again
+                Poll::Pending => self.pending = true,
+            }
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Sends the bytes written to it to an [`AsyncJSONWriter`](struct.AsyncJSONWriter.html),
+/// by chunks
+struct ChunkSender {
+    sender: mpsc::Sender<Vec<u8>>,
+    chunk: Vec<u8>,
+    capacity: usize,
+}
+
+impl ChunkSender {
+    /// Waits until the previous chunk has been taken, to send the current one
+    fn send_chunk(&mut self) -> io::Result<()> {
+        let next_chunk = Vec::with_capacity(self.capacity);
+        let chunk = std::mem::replace(&mut self.chunk, next_chunk);
+        // The receiver is dropped when the writer fails, or the future is cancelled
+        self.sender
+            .blocking_send(chunk)
+            .map_err(|_| io::Error::from(io::ErrorKind::BrokenPipe))
+    }
+}
+
+impl io::Write for ChunkSender {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.chunk.extend_from_slice(buf);
+        if self.chunk.len() >= self.capacity {
+            self.send_chunk()?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Serializes a value to an asynchronous writer, and flushes it
+pub async fn write_json_async<T, W>(value: &T, writer: &mut W) -> io::Result<()>
+where
+    T: JSONValue + ?Sized,
+    W: AsyncWrite + Unpin,
+{
+    let mut json_writer = AsyncJSONWriter::new(writer);
+    json_writer.write_value(value).await?;
+    json_writer.flush().await
+}
+
+/// Serializes a stream of values as a JSON list to an asynchronous writer, and flushes it.
+/// This is the asynchronous analogue of serializing a `RefCell<Iterator>`.
+pub async fn write_json_stream<T, S, W>(stream: S, writer: &mut W) -> io::Result<()>
+where
+    T: JSONValue,
+    S: Stream<Item = T>,
+    W: AsyncWrite + Unpin,
+{
+    let mut json_writer = AsyncJSONWriter::new(writer);
+    json_writer.write_stream(stream).await?;
+    json_writer.flush().await
+}
+
+#[cfg(test)]
+mod tests {
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+    use crate::error::JSONError;
+    use crate::format::NonFiniteFloats;
+    use crate::write::JSONWrite;
+    use crate::*;
+    use futures::executor::block_on;
+    use futures::stream;
+    use std::cell::RefCell;
+    use std::pin::Pin;
+    use std::task::{Context, Poll};
+
+    /// Records the size of every write it receives
+    struct ChunkRecorder {
+        data: Vec<u8>,
+        chunks: Vec<usize>,
+    }
+
+    impl AsyncWrite for ChunkRecorder {
+        fn poll_write(
+            mut self: Pin<&mut Self>,
+            _cx: &mut Context,
+            buf: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            self.data.extend_from_slice(buf);
+            self.chunks.push(buf.len());
+            Poll::Ready(Ok(buf.len()))
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    /// Accepts everything, but is not ready the first time it is polled
+    struct PendingOnce {
+        data: Vec<u8>,
+        polls: usize,
+    }
+
+    impl AsyncWrite for PendingOnce {
+        fn poll_write(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context,
+            buf: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            self.polls += 1;
+            if self.polls == 1 {
+                cx.waker().wake_by_ref();
+                return Poll::Pending;
+            }
+            self.data.extend_from_slice(buf);
+            Poll::Ready(Ok(buf.len()))
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    /// Runs a future in a tokio runtime, that has a blocking thread pool
+    fn block_on_tokio<F: std::future::Future>(future: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(future)
+    }
+
+    #[test]
+    fn test_value() {
+        let mut out = vec![];
+        block_on(write_json_async(&vec![1, 2, 3], &mut out)).unwrap();
+        assert_eq!(b"[1,2,3]".to_vec(), out);
+    }
+
+    #[test]
+    fn test_empty_stream() {
+        let mut out = vec![];
+        block_on(write_json_stream(stream::iter(Vec::<u8>::new()), &mut out)).unwrap();
+        assert_eq!(b"[]".to_vec(), out);
+    }
+
+    #[test]
+    fn test_bounded_buffer() {
+        let mut recorder = ChunkRecorder {
+            data: vec![],
+            chunks: vec![],
+        };
+        let mut writer = AsyncJSONWriter::with_capacity(10, &mut recorder);
+        let values = stream::iter((0..100).map(|i| json_object! { i }));
+        block_on(async {
+            writer.write_stream(values).await?;
+            writer.flush().await
+        })
+        .unwrap();
+        let expected: Vec<String> = (0..100).map(|i| format!(r#"{{"i":{}}}"#, i)).collect();
+        assert_eq!(
+            format!("[{}]", expected.join(",")).into_bytes(),
+            recorder.data
+        );
+        assert!(recorder.chunks.len() > 1);
+        assert!(recorder.chunks.iter().all(|&size| size <= 10 + 10));
+    }
+
+    #[test]
+    fn test_bounded_value() {
+        let mut recorder = ChunkRecorder {
+            data: vec![],
+            chunks: vec![],
+        };
+        let mut writer = AsyncJSONWriter::with_capacity(10, &mut recorder);
+        let value: Vec<u32> = (0..100).collect();
+        block_on(async {
+            writer.write_value(&value).await?;
+            writer.flush().await
+        })
+        .unwrap();
+        assert_eq!(value.to_json_buffer(), recorder.data);
+        assert!(recorder.chunks.len() > 10);
+        assert!(recorder.chunks.iter().all(|&size| size <= 10 + 3));
+    }
+
+    #[test]
+    fn test_pending_writer() {
+        let mut out = PendingOnce {
+            data: vec![],
+            polls: 0,
+        };
+        let mut writer = AsyncJSONWriter::with_capacity(10, &mut out);
+        let value: Vec<u32> = (0..100).collect();
+        block_on(async {
+            writer.write_value(&value).await?;
+            writer.flush().await
+        })
+        .unwrap();
+        assert_eq!(value.to_json_buffer(), out.data);
+        // Once the writer is pending, the rest of the value is sent at once
+        assert_eq!(2, out.polls);
+    }
+
+    #[test]
+    fn test_error_discards_partial_value() {
+        let mut strict = JSONFormatter::compact().non_finite_floats(NonFiniteFloats::Error);
+        let mut out = vec![];
+        let mut writer = AsyncJSONWriter::new(&mut out);
+        block_on(async {
+            writer.write_value(&json_list![1]).await?;
+            let result = writer
+                .write_value_formatted(&json_list![2, f64::NAN], &mut strict)
+                .await;
+            assert_eq!(io::ErrorKind::InvalidData, result.unwrap_err().kind());
+            writer.flush().await
+        })
+        .unwrap();
+        assert_eq!(b"[1]".to_vec(), out);
+
+        // Only the part of the value that has already been sent is kept
+        let mut out = vec![];
+        let mut writer = AsyncJSONWriter::with_capacity(4, &mut out);
+        block_on(async {
+            let result = writer
+                .write_value_formatted(&json_list![1, 2, 3, f64::NAN], &mut strict)
+                .await;
+            assert!(result.is_err());
+            writer.flush().await
+        })
+        .unwrap();
+        // The output is not valid JSON, and has to be discarded
+        assert_eq!(b"[1,2".to_vec(), out);
+
+        // The separator of a failed element of a stream is discarded with it
+        let mut out = vec![];
+        let mut writer = AsyncJSONWriter::new(&mut out);
+        block_on(async {
+            let values = stream::iter(vec![1., f64::NAN]);
+            let result = writer.write_stream_formatted(values, &mut strict).await;
+            assert!(result.is_err());
+            writer.flush().await
+        })
+        .unwrap();
+        assert_eq!(b"[1".to_vec(), out);
+    }
+
+    #[test]
+    fn test_chunked() {
+        let mut recorder = ChunkRecorder {
+            data: vec![],
+            chunks: vec![],
+        };
+        let mut writer = AsyncJSONWriter::with_capacity(16, &mut recorder);
+        block_on_tokio(async {
+            writer.write_value(&true).await?;
+            writer
+                .write_value_chunked(RefCell::new((0..1000).map(|i| json_object! { i })))
+                .await?;
+            writer.flush().await
+        })
+        .unwrap();
+        let expected: Vec<String> = (0..1000).map(|i| format!(r#"{{"i":{}}}"#, i)).collect();
+        assert_eq!(
+            format!("true[{}]", expected.join(",")).into_bytes(),
+            recorder.data
+        );
+        assert!(recorder.chunks.iter().all(|&size| size <= 16 + 10));
+    }
+
+    #[test]
+    fn test_chunked_errors() {
+        struct Panics;
+
+        impl JSONValue for Panics {
+            fn write_json<W: JSONWrite>(&self, _w: &mut W) -> Result<(), W::Error> {
+                panic!("cannot serialize")
+            }
+        }
+
+        let mut out = vec![];
+        let mut writer = AsyncJSONWriter::new(&mut out);
+        let result = block_on_tokio(writer.write_value_chunked(Panics));
+        assert_eq!(io::ErrorKind::Other, result.unwrap_err().kind());
+
+        struct Fails;
+
+        impl JSONValue for Fails {
+            fn write_json<W: JSONWrite>(&self, _w: &mut W) -> Result<(), W::Error> {
+                Err(JSONError::NonFiniteFloat.into())
+            }
+        }
+
+        let result = block_on_tokio(writer.write_value_chunked(Fails));
+        assert_eq!(io::ErrorKind::InvalidData, result.unwrap_err().kind());
+    }
+
+    #[test]
+    fn test_stream_pretty() {
+        let mut out = vec![];
+        let mut writer = AsyncJSONWriter::new(&mut out);
+        block_on(async {
+            let values = stream::iter(vec![json_list![], json_list![]]);
+            writer
+                .write_stream_formatted(values, &mut JSONFormatter::pretty())
+                .await?;
+            writer.flush().await
+        })
+        .unwrap();
+        assert_eq!(b"[\n  [],\n  []\n]".to_vec(), out);
+    }
+}
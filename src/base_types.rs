@@ -1,35 +1,166 @@
 //! Serialization of numbers, booleans, and null
 
+use super::format::JSONFormatter;
+use super::string::JSONString;
+use super::write::JSONWrite;
 use super::JSONValue;
-use std::io;
+
+/// Number of decimal digits of an integer
+#[inline]
+fn digits_len(n: u128) -> usize {
+    n.checked_ilog10().map_or(1, |log| log as usize + 1)
+}
+
+/// Sign and absolute value of an integer
+#[inline]
+fn signed_magnitude(n: i128) -> (bool, u128) {
+    (n < 0, n.unsigned_abs())
+}
+
+/// Widens an unsigned integer to the (negative, magnitude) form used by the integer writers
+#[inline]
+fn unsigned_magnitude(n: u128) -> (bool, u128) {
+    (false, n)
+}
+
+mod private {
+    /// Prevents [`JSONInteger`](../trait.JSONInteger.html) from being implemented
+    /// outside of this crate
+    pub trait Sealed {}
+}
+
+/// An integer type, that can be wrapped in an [`AsString`](struct.AsString.html).
+///
+/// This trait is sealed: it is implemented for all the primitive integer types
+/// and references to them, and cannot be implemented outside of this crate.
+pub trait JSONInteger: JSONValue + private::Sealed {}
+
+impl<T: JSONInteger> private::Sealed for &T {}
+
+impl<T: JSONInteger> JSONInteger for &T {}
 
 macro_rules! impl_json_for_int {
-    ( $( $json_type:ty ),* ) => {
+    ( $magnitude:ident as $wide:ty : $( $json_type:ty ),* ) => {
         $(
+            impl private::Sealed for $json_type {}
+
+            impl JSONInteger for $json_type {}
+
             impl JSONValue for $json_type {
                 #[inline(always)]
-                fn write_json<W: io::Write>(&self, w: &mut W) -> io::Result<()> {
-                    itoa::write(w, *self).map(|_| ())
+                fn write_json<W: JSONWrite>(&self, w: &mut W) -> Result<(), W::Error> {
+                    w.write_all(itoa::Buffer::new().format(*self).as_bytes())
+                }
+
+                #[inline]
+                fn write_json_formatted<W: JSONWrite>(
+                    &self,
+                    w: &mut W,
+                    f: &mut JSONFormatter,
+                ) -> Result<(), W::Error> {
+                    let (_negative, abs) = $magnitude(*self as $wide);
+                    if f.quotes_integer(core::mem::size_of::<$json_type>(), abs) {
+                        AsString(*self).write_json(w)
+                    } else {
+                        self.write_json(w)
+                    }
+                }
+
+                #[inline]
+                fn json_len(&self) -> Option<usize> {
+                    let (negative, abs) = $magnitude(*self as $wide);
+                    Some(negative as usize + digits_len(abs))
                 }
             }
         )*
     };
 }
 
-impl_json_for_int!(i8, i16, i32, i64, i128, isize, u8, u16, u32, u64, u128, usize);
+impl_json_for_int!(signed_magnitude as i128: i8, i16, i32, i64, i128, isize);
+impl_json_for_int!(unsigned_magnitude as u128: u8, u16, u32, u64, u128, usize);
+
+/// Serializes an integer as a JSON string: `AsString(42)` is written as `"42"`.
+///
+/// JavaScript represents all numbers as 64-bit floats, and silently loses precision on
+/// integers larger than 2<sup>53</sup>. 64-bit identifiers are thus often sent as strings.
+/// Only [integers](trait.JSONInteger.html) can be wrapped, since their representation
+/// never needs escaping, and does not depend on the options of a formatter.
+///
+/// # Examples
+///
+/// ```
+/// use json_in_type::*;
+/// use json_in_type::base_types::AsString;
+///
+/// let id = 12345678901234567890u64;
+/// assert_eq!(r#"{"id":"12345678901234567890"}"#, json_object! { id: AsString(id) }.to_json_string());
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct AsString<T: JSONInteger>(pub T);
+
+impl<T: JSONInteger> JSONValue for AsString<T> {
+    #[inline]
+    fn write_json<W: JSONWrite>(&self, w: &mut W) -> Result<(), W::Error> {
+        w.write_all(b"\"")?;
+        self.0.write_json(w)?;
+        w.write_all(b"\"")
+    }
+
+    /// Integers are written the same way by all formatters.
+    /// In particular, they are not quoted a second time when the formatter quotes integers.
+    #[inline]
+    fn write_json_formatted<W: JSONWrite>(
+        &self,
+        w: &mut W,
+        _f: &mut JSONFormatter,
+    ) -> Result<(), W::Error> {
+        self.write_json(w)
+    }
+
+    #[inline]
+    fn json_len(&self) -> Option<usize> {
+        Some(2 + self.0.json_len()?)
+    }
+}
+
+impl<T: JSONInteger> JSONString for AsString<T> {}
 
 macro_rules! impl_json_for_float {
     ( $( $json_type:ty ),* ) => {
         $(
             impl JSONValue for $json_type {
                 #[inline(always)]
-                fn write_json<W: io::Write>(&self, w: &mut W) -> io::Result<()> {
+                fn write_json<W: JSONWrite>(&self, w: &mut W) -> Result<(), W::Error> {
                     if self.is_finite() {
                         w.write_all(ryu_ecmascript::Buffer::new().format(*self).as_bytes())
                     } else {
                         ().write_json(w) // null
                     }
                 }
+
+                #[inline]
+                fn write_json_formatted<W: JSONWrite>(
+                    &self,
+                    w: &mut W,
+                    f: &mut JSONFormatter,
+                ) -> Result<(), W::Error> {
+                    if !self.is_finite() {
+                        f.write_non_finite_float(w, *self as f64)
+                    } else if *self == 0. && f.canonical_numbers() {
+                        w.write_all(b"0") // Without the sign of negative zero
+                    } else {
+                        self.write_json(w)
+                    }
+                }
+
+                #[inline]
+                fn json_len(&self) -> Option<usize> {
+                    if self.is_finite() {
+                        Some(ryu_ecmascript::Buffer::new().format(*self).len())
+                    } else {
+                        ().json_len()
+                    }
+                }
             }
         )*
     };
@@ -38,59 +169,106 @@ macro_rules! impl_json_for_float {
 impl_json_for_float!(f32, f64);
 
 impl JSONValue for () {
-    fn write_json<W: io::Write>(&self, w: &mut W) -> io::Result<()> {
+    fn write_json<W: JSONWrite>(&self, w: &mut W) -> Result<(), W::Error> {
         w.write_all(b"null")
     }
+
+    #[inline]
+    fn json_len(&self) -> Option<usize> {
+        Some(4)
+    }
 }
 
 /// A JSON value representing the value `true`
 /// This is a Zero-Sized type. It takes zero bytes in memory at runtime.
+#[derive(Clone, Copy, Debug)]
 pub struct JSONtrue;
 impl JSONValue for JSONtrue {
     #[inline]
-    fn write_json<W: io::Write>(&self, w: &mut W) -> io::Result<()> {
+    fn write_json<W: JSONWrite>(&self, w: &mut W) -> Result<(), W::Error> {
         w.write_all(b"true")
     }
+
+    #[inline]
+    fn json_len(&self) -> Option<usize> {
+        Some(4)
+    }
 }
 
 /// A JSON value representing the value `false`
 /// This is a Zero-Sized type. It takes zero bytes in memory at runtime.
+#[derive(Clone, Copy, Debug)]
 pub struct JSONfalse;
 impl JSONValue for JSONfalse {
     #[inline]
-    fn write_json<W: io::Write>(&self, w: &mut W) -> io::Result<()> {
+    fn write_json<W: JSONWrite>(&self, w: &mut W) -> Result<(), W::Error> {
         w.write_all(b"false")
     }
+
+    #[inline]
+    fn json_len(&self) -> Option<usize> {
+        Some(5)
+    }
 }
 
 impl JSONValue for bool {
-    fn write_json<W: io::Write>(&self, w: &mut W) -> io::Result<()> {
+    fn write_json<W: JSONWrite>(&self, w: &mut W) -> Result<(), W::Error> {
         if *self {
             JSONtrue.write_json(w)
         } else {
             JSONfalse.write_json(w)
         }
     }
+
+    #[inline]
+    fn json_len(&self) -> Option<usize> {
+        if *self {
+            JSONtrue.json_len()
+        } else {
+            JSONfalse.json_len()
+        }
+    }
 }
 
 impl<T: JSONValue> JSONValue for Option<T> {
-    fn write_json<W: io::Write>(&self, w: &mut W) -> io::Result<()> {
+    fn write_json<W: JSONWrite>(&self, w: &mut W) -> Result<(), W::Error> {
         if let Some(val) = self {
             val.write_json(w)
         } else {
             ().write_json(w)
         }
     }
+
+    fn write_json_formatted<W: JSONWrite>(
+        &self,
+        w: &mut W,
+        f: &mut JSONFormatter,
+    ) -> Result<(), W::Error> {
+        if let Some(val) = self {
+            val.write_json_formatted(w, f)
+        } else {
+            ().write_json(w)
+        }
+    }
+
+    fn json_len(&self) -> Option<usize> {
+        if let Some(val) = self {
+            val.json_len()
+        } else {
+            ().json_len()
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     // Note this useful idiom: importing names from outer (for mod tests) scope.
     use super::*;
+    use crate::*;
 
     #[test]
     fn test_int() {
-        assert_eq!("-1234567890", (-1234567890 as i32).to_json_string());
+        assert_eq!("-1234567890", (-1234567890_i32).to_json_string());
         assert_eq!(
             "1234567890123456789",
             1234567890123456789u64.to_json_string()
@@ -100,11 +278,74 @@ mod tests {
     #[test]
     fn test_float() {
         use std::f64;
-        assert_eq!("-1234567890", (-1234567890 as f64).to_json_string());
+        assert_eq!("-1234567890", (-1234567890_f64).to_json_string());
         assert_eq!("null", (f64::NAN).to_json_string());
         assert_eq!("null", (f64::NEG_INFINITY).to_json_string());
     }
 
+    #[test]
+    fn test_len() {
+        fn check<T: JSONValue>(value: T) {
+            assert_eq!(Some(value.to_json_string().len()), value.json_len());
+        }
+        for &n in &[0, 1, 9, 10, 99, 100, -1, -9, -10, i64::MAX, i64::MIN] {
+            check(n);
+            check(n as f64 / 7.);
+        }
+        check(u128::MAX);
+        check(i128::MIN);
+        check(u8::MAX);
+        check(i8::MIN);
+        check(f32::NAN);
+        check(f64::INFINITY);
+        check(Some(true));
+        check(None::<bool>);
+        check(false);
+        check(());
+    }
+
+    #[test]
+    fn test_as_string() {
+        assert_eq!(r#""-42""#, AsString(-42).to_json_string());
+        assert_eq!(r#""7""#, AsString(&7u8).to_json_string());
+        assert_eq!(Some(5), AsString(-42).json_len());
+        let key = AsString(1u8);
+        assert_eq!(
+            r#"{"1":[]}"#,
+            json_object! { [key]: json_list![] }.to_json_string()
+        );
+    }
+
+    #[test]
+    fn test_as_string_formatted() {
+        use crate::format::{IntegerQuoting, JSONFormatter};
+
+        fn format<T: JSONValue>(value: T, mut f: JSONFormatter) -> String {
+            let mut buffer = vec![];
+            value.write_json_formatted(&mut buffer, &mut f).unwrap();
+            String::from_utf8(buffer).unwrap()
+        }
+
+        let id = AsString(u64::MAX);
+        let quoting = JSONFormatter::compact().integer_quoting(IntegerQuoting::Wide);
+        assert_eq!(r#""18446744073709551615""#, format(id, quoting));
+        assert_eq!(Some(22), id.json_len());
+        assert_eq!(
+            r#"{"18446744073709551615":"18446744073709551615"}"#,
+            format(
+                json_object! { [id]: id },
+                JSONFormatter::canonical().integer_quoting(IntegerQuoting::OutsideSafeRange)
+            )
+        );
+        assert_eq!(
+            "{\n  \"-1\": [\n    \"2\"\n  ]\n}",
+            format(
+                json_object! { [AsString(-1)]: json_list![AsString(2)] },
+                JSONFormatter::pretty()
+            )
+        );
+    }
+
     #[test]
     fn test_bool() {
         assert_eq!("true", true.to_json_string());
@@ -0,0 +1,317 @@
+//! JSON values that are known at compile time
+//!
+//! The macros of this crate concatenate the parts of an object or a list that do not depend
+//! on runtime values, such as keys, `null`, `true`, `false`, and nested constant objects and lists,
+//! into a single byte string, computed at compile time, and written with a single call to
+//! [`write_all`](../write/trait.JSONWrite.html#tymethod.write_all).
+//!
+//! Values that are entirely constant implement [`ConstJSONValue`](trait.ConstJSONValue.html),
+//! and expose their JSON as a constant string.
+//!
+//! # Examples
+//!
+//! ```
+//! use json_in_type::*;
+//! use json_in_type::constant::const_json_str;
+//!
+//! const HEADER: &str = const_json_str(&json!({"ok": true, "error": null, "warnings": []}));
+//! assert_eq!(r#"{"ok":true,"error":null,"warnings":[]}"#, HEADER);
+//! ```
+//!
+//! Numbers and strings are not folded: they are written at runtime, between constant fragments.
+//!
+//! ```
+//! use json_in_type::*;
+//! use json_in_type::constant::ConstJSONValue;
+//!
+//! fn is_constant<T: ConstJSONValue>(_value: &T) {}
+//!
+//! is_constant(&json!({"ok": true, "data": [null, {"x": false}]}));
+//! let with_number = json!({"ok": true, "data": [null, {"x": 1}]});
+//! assert_eq!(r#"{"ok":true,"data":[null,{"x":1}]}"#, with_number.to_json_string());
+//! ```
+
+use super::base_types::{JSONfalse, JSONtrue};
+use super::list::JSONListEnd;
+use super::object::JSONObjectEnd;
+use super::string::{copy_bytes, quoted_key_len, write_quoted};
+use super::JSONValue;
+
+/// A json value whose serialization is known at compile time.
+///
+/// It is implemented by `()`, [`JSONtrue`](../base_types/struct.JSONtrue.html),
+/// [`JSONfalse`](../base_types/struct.JSONfalse.html), and by the objects and lists
+/// created by [`json!`](../macro.json.html), [`json_object!`](../macro.json_object.html)
+/// and [`json_list!`](../macro.json_list.html) when all their contents are constant.
+///
+/// # Examples
+///
+/// ```
+/// use json_in_type::*;
+/// use json_in_type::constant::ConstJSONValue;
+///
+/// fn constant_json<T: ConstJSONValue>(_value: &T) -> &'static str {
+///     T::JSON
+/// }
+///
+/// let status = json_object! { ok: true, "error": null };
+/// assert_eq!(r#"{"ok":true,"error":null}"#, constant_json(&status));
+/// assert_eq!(0, std::mem::size_of_val(&status));
+/// ```
+pub trait ConstJSONValue: JSONValue {
+    /// The json representation of the value, as written by
+    /// [`write_json`](../trait.JSONValue.html#tymethod.write_json)
+    const JSON: &'static str;
+}
+
+/// Returns the json representation of a constant value.
+/// Unlike [`to_json_string`](../trait.JSONValue.html#method.to_json_string),
+/// it can be used to define constants, and does not allocate.
+///
+/// # Examples
+///
+/// ```
+/// use json_in_type::*;
+/// use json_in_type::constant::const_json_str;
+///
+/// const EMPTY_PAGE: &str = const_json_str(&json!({"items": [], "next": null}));
+/// assert_eq!(r#"{"items":[],"next":null}"#, EMPTY_PAGE);
+/// ```
+pub const fn const_json_str<T: ConstJSONValue>(_value: &T) -> &'static str {
+    T::JSON
+}
+
+impl ConstJSONValue for () {
+    const JSON: &'static str = "null";
+}
+
+impl ConstJSONValue for JSONtrue {
+    const JSON: &'static str = "true";
+}
+
+impl ConstJSONValue for JSONfalse {
+    const JSON: &'static str = "false";
+}
+
+impl ConstJSONValue for JSONObjectEnd {
+    const JSON: &'static str = "{}";
+}
+
+impl ConstJSONValue for JSONListEnd {
+    const JSON: &'static str = "[]";
+}
+
+/// Consecutive constant members of an object, or elements of a list, serialized at compile time.
+/// Implemented by types generated by the macros of this crate.
+#[doc(hidden)]
+pub trait ConstFragment {
+    /// The fragment at the start of an object or a list, starting with `{` or `[`
+    const FIRST: &'static str;
+    /// The fragment after other members or elements, starting with a comma
+    const NEXT: &'static str;
+    /// Whether the fragment includes the closing `}` or `]`
+    const END: bool;
+}
+
+/// Length of an object fragment (see [`object_fragment`](fn.object_fragment.html))
+#[doc(hidden)]
+pub const fn object_fragment_len(members: &[(&str, &str)], end: bool) -> usize {
+    let mut len = end as usize;
+    let mut i = 0;
+    while i < members.len() {
+        let (key, json) = members[i];
+        // { or , before the key, and : after it
+        len += 1 + quoted_key_len(key) + 1 + json.len();
+        i += 1;
+    }
+    len
+}
+
+/// The members of an object with the given keys and serialized values,
+/// starting with `start` (either `{` or `,`), and followed by `}` if `end` is true.
+/// `N` must be [`object_fragment_len(members, end)`](fn.object_fragment_len.html).
+#[doc(hidden)]
+pub const fn object_fragment<const N: usize>(
+    members: &[(&str, &str)],
+    start: u8,
+    end: bool,
+) -> [u8; N] {
+    let mut out = [0; N];
+    let mut len = 0;
+    let mut i = 0;
+    while i < members.len() {
+        let (key, json) = members[i];
+        out[len] = if i == 0 { start } else { b',' };
+        let (quoted, quoted_len) = write_quoted(out, len + 1, key);
+        out = quoted;
+        out[quoted_len] = b':';
+        let (copied, copied_len) = copy_bytes(out, quoted_len + 1, json.as_bytes());
+        out = copied;
+        len = copied_len;
+        i += 1;
+    }
+    if end {
+        out[len] = b'}';
+    }
+    out
+}
+
+/// Length of a list fragment (see [`list_fragment`](fn.list_fragment.html))
+#[doc(hidden)]
+pub const fn list_fragment_len(elements: &[&str], end: bool) -> usize {
+    let mut len = end as usize;
+    let mut i = 0;
+    while i < elements.len() {
+        // [ or , before the element
+        len += 1 + elements[i].len();
+        i += 1;
+    }
+    len
+}
+
+/// The elements of a list, serialized, starting with `start` (either `[` or `,`),
+/// and followed by `]` if `end` is true.
+/// `N` must be [`list_fragment_len(elements, end)`](fn.list_fragment_len.html).
+#[doc(hidden)]
+pub const fn list_fragment<const N: usize>(elements: &[&str], start: u8, end: bool) -> [u8; N] {
+    let mut out = [0; N];
+    let mut len = 0;
+    let mut i = 0;
+    while i < elements.len() {
+        out[len] = if i == 0 { start } else { b',' };
+        let (copied, copied_len) = copy_bytes(out, len + 1, elements[i].as_bytes());
+        out = copied;
+        len = copied_len;
+        i += 1;
+    }
+    if end {
+        out[len] = b']';
+    }
+    out
+}
+
+/// Converts a fragment, made of valid UTF-8 strings, back to a string, at compile time
+#[doc(hidden)]
+pub const fn fragment_str(bytes: &'static [u8]) -> &'static str {
+    match core::str::from_utf8(bytes) {
+        Ok(s) => s,
+        Err(_) => panic!("a json fragment is not valid UTF-8"),
+    }
+}
+
+/// Creates a folded object or list from its constant members or elements,
+/// followed by the rest of the object or list.
+#[macro_export]
+#[doc(hidden)]
+macro_rules! inlined_json_fragment {
+    (object : [$(($key:expr, $value:expr))+]) => {
+        inlined_json_fragment!(@object true, [$(($key, $value))+], $crate::object::JSONObjectEnd)
+    };
+    (object : [$(($key:expr, $value:expr))+], next : $next:expr) => {
+        inlined_json_fragment!(@object false, [$(($key, $value))+], $next)
+    };
+    (list : [$(($value:expr))+]) => {
+        inlined_json_fragment!(@list true, [$(($value))+], $crate::list::JSONListEnd)
+    };
+    (list : [$(($value:expr))+], next : $next:expr) => {
+        inlined_json_fragment!(@list false, [$(($value))+], $next)
+    };
+    (@object $end:expr, [$(($key:expr, $value:expr))+], $next:expr) => {{
+        #[derive(Clone, Copy, Debug)]
+        struct InlinedJSONFragment;
+
+        // The members, serialized at compile time
+        impl InlinedJSONFragment {
+            const MEMBERS: &'static [(&'static str, &'static str)] =
+                &[$(($key, $crate::constant::const_json_str(&$value))),+];
+            const LEN: usize =
+                $crate::constant::object_fragment_len(InlinedJSONFragment::MEMBERS, $end);
+            const FIRST_BYTES: [u8; InlinedJSONFragment::LEN] =
+                $crate::constant::object_fragment(InlinedJSONFragment::MEMBERS, b'{', $end);
+            const NEXT_BYTES: [u8; InlinedJSONFragment::LEN] =
+                $crate::constant::object_fragment(InlinedJSONFragment::MEMBERS, b',', $end);
+        }
+
+        impl $crate::constant::ConstFragment for InlinedJSONFragment {
+            const FIRST: &'static str =
+                $crate::constant::fragment_str(&InlinedJSONFragment::FIRST_BYTES);
+            const NEXT: &'static str =
+                $crate::constant::fragment_str(&InlinedJSONFragment::NEXT_BYTES);
+            const END: bool = $end;
+        }
+
+        $crate::object::JSONObjectFragment {
+            fragment: InlinedJSONFragment,
+            entries: json_object! { $([inlined_json_key!(key_str: $key)]: $value),+ },
+            next: $next,
+        }
+    }};
+    (@list $end:expr, [$(($value:expr))+], $next:expr) => {{
+        #[derive(Clone, Copy, Debug)]
+        struct InlinedJSONFragment;
+
+        // The elements, serialized at compile time
+        impl InlinedJSONFragment {
+            const ELEMENTS: &'static [&'static str] =
+                &[$($crate::constant::const_json_str(&$value)),+];
+            const LEN: usize =
+                $crate::constant::list_fragment_len(InlinedJSONFragment::ELEMENTS, $end);
+            const FIRST_BYTES: [u8; InlinedJSONFragment::LEN] =
+                $crate::constant::list_fragment(InlinedJSONFragment::ELEMENTS, b'[', $end);
+            const NEXT_BYTES: [u8; InlinedJSONFragment::LEN] =
+                $crate::constant::list_fragment(InlinedJSONFragment::ELEMENTS, b',', $end);
+        }
+
+        impl $crate::constant::ConstFragment for InlinedJSONFragment {
+            const FIRST: &'static str =
+                $crate::constant::fragment_str(&InlinedJSONFragment::FIRST_BYTES);
+            const NEXT: &'static str =
+                $crate::constant::fragment_str(&InlinedJSONFragment::NEXT_BYTES);
+            const END: bool = $end;
+        }
+
+        $crate::list::JSONListFragment {
+            fragment: InlinedJSONFragment,
+            elements: json_list![$($value),+],
+            next: $next,
+        }
+    }};
+}
+
+#[cfg(test)]
+mod tests {
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+    use crate::*;
+
+    #[test]
+    fn test_object_fragment() {
+        const MEMBERS: &[(&str, &str)] = &[("a", "null"), ("b\"", "[true]")];
+        const LEN: usize = object_fragment_len(MEMBERS, true);
+        const FIRST: [u8; LEN] = object_fragment(MEMBERS, b'{', true);
+        assert_eq!(br#"{"a":null,"b\"":[true]}"#, &FIRST);
+        const NEXT_LEN: usize = object_fragment_len(MEMBERS, false);
+        const NEXT: [u8; NEXT_LEN] = object_fragment(MEMBERS, b',', false);
+        assert_eq!(br#","a":null,"b\"":[true]"#, &NEXT);
+    }
+
+    #[test]
+    fn test_list_fragment() {
+        const ELEMENTS: &[&str] = &["null", "{}", "false"];
+        const LEN: usize = list_fragment_len(ELEMENTS, true);
+        const FIRST: [u8; LEN] = list_fragment(ELEMENTS, b'[', true);
+        assert_eq!(b"[null,{},false]", &FIRST);
+        const NEXT_LEN: usize = list_fragment_len(ELEMENTS, false);
+        const NEXT: [u8; NEXT_LEN] = list_fragment(ELEMENTS, b',', false);
+        assert_eq!(b",null,{},false", &NEXT);
+    }
+
+    #[test]
+    fn test_const_json_str() {
+        const JSON: &str = const_json_str(&json!([null, [true, {"a": false}]]));
+        assert_eq!(r#"[null,[true,{"a":false}]]"#, JSON);
+        assert_eq!(JSON, json!([null, [true, {"a": false}]]).to_json_string());
+        assert_eq!("{}", const_json_str(&json_object! {}));
+        assert_eq!("[]", const_json_str(&json_list![]));
+    }
+}
@@ -0,0 +1,205 @@
+//! Dynamic dispatch over values of different types.
+//!
+//! [`JSONValue`](../trait.JSONValue.html) is generic over the writer, so it cannot be made into
+//! a trait object. [`DynJSONValue`](trait.DynJSONValue.html) is implemented by every `JSONValue`,
+//! and writes to a type-erased [`DynWriter`](struct.DynWriter.html) instead.
+//! `dyn DynJSONValue` itself implements `JSONValue`, as do boxes, references and `Arc`s to it,
+//! so values of different shapes can be stored in the same collection and written anywhere.
+//!
+//! # Examples
+//!
+//! ```
+//! use json_in_type::*;
+//! use json_in_type::dynamic::DynJSONValue;
+//!
+//! let x = 1;
+//! let values: Vec<Box<dyn DynJSONValue>> = vec![
+//!     Box::new(json_object! { x }),
+//!     Box::new(json_list![true, "two"]),
+//!     Box::new(3.5),
+//! ];
+//!
+//! assert_eq!(r#"[{"x":1},[true,"two"],3.5]"#, values.to_json_string());
+//! ```
+
+use super::error::JSONError;
+use super::format::JSONFormatter;
+use super::write::JSONWrite;
+use super::JSONValue;
+
+/// The object-safe counterpart of [`JSONValue`](../trait.JSONValue.html),
+/// implemented by every `JSONValue`.
+///
+/// Its methods mirror the ones of `JSONValue`, and need not be called directly:
+/// write a `dyn DynJSONValue` with the methods of `JSONValue` instead.
+pub trait DynJSONValue {
+    /// Writes the value to a type-erased writer,
+    /// like [`JSONValue::write_json`](../trait.JSONValue.html#tymethod.write_json)
+    fn write_json_dyn(&self, w: &mut DynWriter) -> Result<(), DynWriteError>;
+
+    /// Writes the value to a type-erased writer, using the given formatter, like
+    /// [`JSONValue::write_json_formatted`](../trait.JSONValue.html#method.write_json_formatted)
+    fn write_json_formatted_dyn(
+        &self,
+        w: &mut DynWriter,
+        f: &mut JSONFormatter,
+    ) -> Result<(), DynWriteError>;
+
+    /// Like [`JSONValue::json_len`](../trait.JSONValue.html#method.json_len)
+    fn json_len_dyn(&self) -> Option<usize>;
+}
+
+impl<T: JSONValue> DynJSONValue for T {
+    fn write_json_dyn(&self, w: &mut DynWriter) -> Result<(), DynWriteError> {
+        self.write_json(w)
+    }
+
+    fn write_json_formatted_dyn(
+        &self,
+        w: &mut DynWriter,
+        f: &mut JSONFormatter,
+    ) -> Result<(), DynWriteError> {
+        self.write_json_formatted(w, f)
+    }
+
+    fn json_len_dyn(&self) -> Option<usize> {
+        self.json_len()
+    }
+}
+
+/// A [`JSONWrite`](../write/trait.JSONWrite.html) whose type has been erased,
+/// to which a [`DynJSONValue`](trait.DynJSONValue.html) is written.
+/// It is created when a `dyn DynJSONValue` is written to another writer.
+pub struct DynWriter<'a> {
+    w: &'a mut dyn ErasedWrite,
+}
+
+impl JSONWrite for DynWriter<'_> {
+    type Error = DynWriteError;
+
+    #[inline]
+    fn write_all(&mut self, buf: &[u8]) -> Result<(), DynWriteError> {
+        self.w.write_erased(buf)
+    }
+}
+
+/// The error of a [`DynWriter`](struct.DynWriter.html).
+/// The error of the underlying writer is kept aside,
+/// and returned once the value has been written.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DynWriteError(Option<JSONError>);
+
+impl From<JSONError> for DynWriteError {
+    fn from(err: JSONError) -> Self {
+        DynWriteError(Some(err))
+    }
+}
+
+trait ErasedWrite {
+    fn write_erased(&mut self, buf: &[u8]) -> Result<(), DynWriteError>;
+}
+
+/// A writer, and the first error it returned
+struct Erased<'a, W: JSONWrite> {
+    w: &'a mut W,
+    error: Option<W::Error>,
+}
+
+impl<W: JSONWrite> ErasedWrite for Erased<'_, W> {
+    fn write_erased(&mut self, buf: &[u8]) -> Result<(), DynWriteError> {
+        self.w.write_all(buf).map_err(|err| {
+            self.error = Some(err);
+            DynWriteError(None)
+        })
+    }
+}
+
+/// Erases the type of `w` to call `write`, and restores the error it returned
+fn write_erased<W, F>(w: &mut W, write: F) -> Result<(), W::Error>
+where
+    W: JSONWrite,
+    F: FnOnce(&mut DynWriter) -> Result<(), DynWriteError>,
+{
+    let mut erased = Erased { w, error: None };
+    match write(&mut DynWriter { w: &mut erased }) {
+        Ok(()) => Ok(()),
+        Err(DynWriteError(Some(err))) => Err(err.into()),
+        Err(DynWriteError(None)) => Err(erased
+            .error
+            .expect("a DynWriter fails only when its writer does")),
+    }
+}
+
+macro_rules! impl_json_for_dyn {
+    ( $( $bounds:path ),* ) => {
+        impl JSONValue for dyn DynJSONValue $( + $bounds )* + '_ {
+            fn write_json<W: JSONWrite>(&self, w: &mut W) -> Result<(), W::Error> {
+                write_erased(w, |w| self.write_json_dyn(w))
+            }
+
+            fn write_json_formatted<W: JSONWrite>(
+                &self,
+                w: &mut W,
+                f: &mut JSONFormatter,
+            ) -> Result<(), W::Error> {
+                write_erased(w, |w| self.write_json_formatted_dyn(w, f))
+            }
+
+            fn json_len(&self) -> Option<usize> {
+                self.json_len_dyn()
+            }
+        }
+    };
+}
+
+impl_json_for_dyn!();
+impl_json_for_dyn!(Send);
+impl_json_for_dyn!(Send, Sync);
+
+#[cfg(test)]
+mod tests {
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+    use crate::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_heterogeneous() {
+        let name = "x";
+        let values: Vec<Box<dyn DynJSONValue + Send + Sync>> = vec![
+            Box::new(json_object! { name, ok: true }),
+            Box::new(json_list![1, null]),
+            Box::new(vec!["a"]),
+        ];
+        let expected = r#"[{"name":"x","ok":true},[1,null],["a"]]"#;
+        assert_eq!(expected, values.to_json_string());
+        assert_eq!(Some(expected.len()), values.json_len());
+        assert_eq!(
+            "[\n  {\n    \"name\": \"x\",\n    \"ok\": true\n  },\n  [\n    1,\n    null\n  ],\n  [\n    \"a\"\n  ]\n]",
+            values.to_json_string_pretty()
+        );
+
+        let shared: Arc<dyn DynJSONValue> = Arc::new(json_object! { shared: 1 });
+        let borrowed: &dyn DynJSONValue = &42;
+        let obj = json_object! { shared: shared.clone(), borrowed };
+        assert_eq!(
+            r#"{"shared":{"shared":1},"borrowed":42}"#,
+            obj.to_json_string()
+        );
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_errors() {
+        use crate::format::NonFiniteFloats;
+
+        let value: Box<dyn DynJSONValue> = Box::new(json_list![1., f64::NAN]);
+        let mut f = JSONFormatter::compact().non_finite_floats(NonFiniteFloats::Error);
+        let err = value.write_json_formatted(&mut vec![], &mut f).unwrap_err();
+        assert_eq!(std::io::ErrorKind::InvalidData, err.kind());
+
+        let mut full = [0u8; 3];
+        let err = value.write_json(&mut &mut full[..]).unwrap_err();
+        assert_eq!(std::io::ErrorKind::WriteZero, err.kind());
+    }
+}
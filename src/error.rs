@@ -0,0 +1,60 @@
+//! Errors that can happen while serializing a value,
+//! independently of the destination it is written to
+
+use core::fmt;
+
+/// A value that cannot be represented in JSON with the chosen options.
+///
+/// Every [`JSONWrite::Error`](../write/trait.JSONWrite.html#associatedtype.Error)
+/// can be created from a `JSONError`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum JSONError {
+    /// A NaN or infinite float, when non-finite floats are
+    /// [rejected](../format/enum.NonFiniteFloats.html#variant.Error)
+    NonFiniteFloat,
+    /// A call to a [`JSONStreamWriter`](../stream/struct.JSONStreamWriter.html)
+    /// that does not fit the structure of the document being written,
+    /// such as a value where an object key is expected
+    InvalidStructure,
+    /// A server-sent [event](../sse/struct.Event.html) whose name or id
+    /// contains a character that would break its framing
+    InvalidEventField,
+    /// A call to a [`JSONStreamWriter`](../stream/struct.JSONStreamWriter.html)
+    /// after one of its writes failed, leaving the document incomplete
+    Poisoned,
+}
+
+impl fmt::Display for JSONError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            JSONError::NonFiniteFloat => "non-finite floats cannot be represented in JSON",
+            JSONError::InvalidStructure => "the JSON document would not be well-formed",
+            JSONError::InvalidEventField => "event names and ids cannot contain line breaks",
+            JSONError::Poisoned => "a previous write failed, and left the JSON document incomplete",
+        })
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for JSONError {}
+
+#[cfg(feature = "std")]
+impl From<JSONError> for std::io::Error {
+    fn from(err: JSONError) -> Self {
+        let kind = match err {
+            JSONError::NonFiniteFloat => std::io::ErrorKind::InvalidData,
+            JSONError::InvalidStructure | JSONError::InvalidEventField => {
+                std::io::ErrorKind::InvalidInput
+            }
+            JSONError::Poisoned => std::io::ErrorKind::Other,
+        };
+        std::io::Error::new(kind, err)
+    }
+}
+
+impl From<JSONError> for fmt::Error {
+    fn from(_err: JSONError) -> Self {
+        fmt::Error
+    }
+}
@@ -0,0 +1,527 @@
+//! Configurable layout of the produced JSON, such as pretty-printing
+//!
+//! [`write_json`](../trait.JSONValue.html#tymethod.write_json) always produces
+//! the most compact JSON possible, as fast as possible.
+//! When a different layout is needed, a [`JSONFormatter`](struct.JSONFormatter.html)
+//! can be passed to
+//! [`write_json_formatted`](../trait.JSONValue.html#method.write_json_formatted).
+//!
+//! # Examples
+//!
+//! ```
+//! use json_in_type::*;
+//! use json_in_type::format::JSONFormatter;
+//!
+//! let obj = json_object! { x: json_list![1, 2], y: json_object!{} };
+//! let mut formatter = JSONFormatter::pretty().indent("    ");
+//! let mut buffer: Vec<u8> = vec![];
+//! obj.write_json_formatted(&mut buffer, &mut formatter).unwrap();
+//!
+//! assert_eq!(
+//!     "{\n    \"x\": [\n        1,\n        2\n    ],\n    \"y\": {}\n}",
+//!     String::from_utf8(buffer).unwrap()
+//! );
+//! ```
+
+use super::error::JSONError;
+use super::string::write_escaped;
+use super::write::JSONWrite;
+
+/// What to write for floating-point numbers that have no JSON representation:
+/// NaN, infinity and minus infinity.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NonFiniteFloats {
+    /// Write `null`. This is what [`write_json`](../trait.JSONValue.html#tymethod.write_json) does.
+    Null,
+    /// Fail with [`JSONError::NonFiniteFloat`](../error/enum.JSONError.html#variant.NonFiniteFloat)
+    Error,
+    /// Write the strings `"NaN"`, `"Infinity"` and `"-Infinity"`
+    String,
+    /// Write the bare tokens `NaN`, `Infinity` and `-Infinity`.
+    /// This is not valid JSON, but is accepted by JSON5 and JavaScript parsers.
+    Bare,
+}
+
+/// Which integers to write as JSON strings.
+///
+/// JavaScript represents all numbers as 64-bit floats, and silently loses precision
+/// on integers whose absolute value is larger than 2<sup>53</sup>-1 (`Number.MAX_SAFE_INTEGER`).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IntegerQuoting {
+    /// Write all integers as JSON numbers. This is what
+    /// [`write_json`](../trait.JSONValue.html#tymethod.write_json) does.
+    Never,
+    /// Write integers that cannot be represented exactly by a JavaScript number as strings
+    OutsideSafeRange,
+    /// Write all integers of types larger than 32 bits as strings,
+    /// so that the type of a field does not depend on its value
+    Wide,
+}
+
+/// Which characters to escape in JSON strings
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StringEscaping {
+    /// Escape only the characters that cannot appear in a JSON string:
+    /// quotes, backslashes, and control characters.
+    /// This is what [`write_json`](../trait.JSONValue.html#tymethod.write_json) does.
+    Minimal,
+    /// Also escape all non-ASCII characters as `\uXXXX` sequences,
+    /// using UTF-16 surrogate pairs for characters outside of the basic multilingual plane,
+    /// so that the output is pure 7-bit ASCII
+    Ascii,
+    /// Also escape `<`, `>`, `&`, `/`, U+2028 and U+2029,
+    /// so that the output can be embedded in an HTML `<script>` tag.
+    /// A string containing `</script>` cannot close the tag, and the output
+    /// is valid JavaScript even for parsers that predate ES2019.
+    Html,
+    /// The escaping of [RFC 8785](https://www.rfc-editor.org/rfc/rfc8785#section-3.2.2.2):
+    /// like `Minimal`, but the DEL character is not escaped.
+    Canonical,
+}
+
+/// The largest integer that can be represented exactly in an IEEE-754 double
+pub const MAX_SAFE_INTEGER: u128 = (1 << 53) - 1;
+
+/// Controls the whitespace that is written between the tokens of a JSON document.
+///
+/// The formatter also keeps track of the current nesting depth,
+/// so a single formatter should be used for a whole document.
+#[derive(Clone, Debug)]
+pub struct JSONFormatter {
+    indent: &'static str,
+    newline: &'static str,
+    space_after_colon: bool,
+    non_finite_floats: NonFiniteFloats,
+    integer_quoting: IntegerQuoting,
+    string_escaping: StringEscaping,
+    sort_keys: bool,
+    canonical_numbers: bool,
+    depth: usize,
+}
+
+impl JSONFormatter {
+    /// A formatter that does not write any whitespace: `{"x":[1,2]}`
+    pub fn compact() -> JSONFormatter {
+        JSONFormatter {
+            indent: "",
+            newline: "",
+            space_after_colon: false,
+            non_finite_floats: NonFiniteFloats::Null,
+            integer_quoting: IntegerQuoting::Never,
+            string_escaping: StringEscaping::Minimal,
+            sort_keys: false,
+            canonical_numbers: false,
+            depth: 0,
+        }
+    }
+
+    /// A formatter that writes every value on its own line,
+    /// indented by two spaces per nesting level.
+    pub fn pretty() -> JSONFormatter {
+        JSONFormatter {
+            indent: "  ",
+            newline: "\n",
+            space_after_colon: true,
+            non_finite_floats: NonFiniteFloats::Null,
+            integer_quoting: IntegerQuoting::Never,
+            string_escaping: StringEscaping::Minimal,
+            sort_keys: false,
+            canonical_numbers: false,
+            depth: 0,
+        }
+    }
+
+    /// A formatter that produces the canonical JSON defined by
+    /// [RFC 8785](https://www.rfc-editor.org/rfc/rfc8785) (JSON Canonicalization Scheme).
+    /// Every value has a single representation, which makes the output suitable for
+    /// hashing and signing:
+    ///
+    ///  - object members are sorted by the UTF-16 code units of their keys,
+    ///  - strings use the escaping of [`StringEscaping::Canonical`](enum.StringEscaping.html#variant.Canonical),
+    ///  - floats are written like JavaScript's `Number.prototype.toString`, and `-0` as `0`,
+    ///  - non-finite floats are [rejected](enum.NonFiniteFloats.html#variant.Error),
+    ///  - integers are written as numbers, with all their digits.
+    ///
+    /// The RFC leaves it to applications to write the integers that a JavaScript number
+    /// cannot represent exactly as strings. They can opt in with
+    /// [`integer_quoting`](#method.integer_quoting)`(IntegerQuoting::OutsideSafeRange)`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use json_in_type::*;
+    /// use json_in_type::format::JSONFormatter;
+    ///
+    /// let obj = json_object! { zebra: -0.0, apple: json_object! { é: "\t", e: 1e21 } };
+    /// let mut buffer: Vec<u8> = vec![];
+    /// obj.write_json_formatted(&mut buffer, &mut JSONFormatter::canonical()).unwrap();
+    /// assert_eq!(r#"{"apple":{"e":1e+21,"é":"\t"},"zebra":0}"#.as_bytes(), &buffer[..]);
+    /// ```
+    #[cfg(feature = "alloc")]
+    pub fn canonical() -> JSONFormatter {
+        JSONFormatter {
+            non_finite_floats: NonFiniteFloats::Error,
+            string_escaping: StringEscaping::Canonical,
+            sort_keys: true,
+            canonical_numbers: true,
+            ..JSONFormatter::compact()
+        }
+    }
+
+    /// Sets the string that is repeated once per nesting level at the beginning of each line.
+    /// It has no effect if the newline string is empty.
+    pub fn indent(mut self, indent: &'static str) -> JSONFormatter {
+        self.indent = indent;
+        self
+    }
+
+    /// Sets the string that separates lines, such as `"\n"` or `"\r\n"`.
+    /// An empty string writes everything on a single line.
+    pub fn newline(mut self, newline: &'static str) -> JSONFormatter {
+        self.newline = newline;
+        self
+    }
+
+    /// Whether to write a space after the colon that separates an object key from its value
+    pub fn space_after_colon(mut self, space_after_colon: bool) -> JSONFormatter {
+        self.space_after_colon = space_after_colon;
+        self
+    }
+
+    /// Sets how NaN and infinite floats are written. The default is to write `null`.
+    pub fn non_finite_floats(mut self, non_finite_floats: NonFiniteFloats) -> JSONFormatter {
+        self.non_finite_floats = non_finite_floats;
+        self
+    }
+
+    /// Sets which integers are written as strings. The default is to never quote them.
+    pub fn integer_quoting(mut self, integer_quoting: IntegerQuoting) -> JSONFormatter {
+        self.integer_quoting = integer_quoting;
+        self
+    }
+
+    /// Sets which characters are escaped in strings. The default is minimal escaping.
+    pub fn string_escaping(mut self, string_escaping: StringEscaping) -> JSONFormatter {
+        self.string_escaping = string_escaping;
+        self
+    }
+
+    /// Whether to write object members sorted by the UTF-16 code units of their keys,
+    /// instead of in their natural order. Sorting requires buffering the members of objects.
+    #[cfg(feature = "alloc")]
+    pub fn sort_keys(mut self, sort_keys: bool) -> JSONFormatter {
+        self.sort_keys = sort_keys;
+        self
+    }
+
+    /// Whether object members are written sorted by key
+    #[inline]
+    pub fn sorts_keys(&self) -> bool {
+        self.sort_keys
+    }
+
+    /// Whether negative zero is written as `0`
+    #[inline]
+    pub(crate) fn canonical_numbers(&self) -> bool {
+        self.canonical_numbers
+    }
+
+    #[inline]
+    fn write_line_start<W: JSONWrite>(&self, w: &mut W) -> Result<(), W::Error> {
+        if !self.newline.is_empty() {
+            w.write_all(self.newline.as_bytes())?;
+            for _ in 0..self.depth {
+                w.write_all(self.indent.as_bytes())?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Called before the first member of an object
+    #[inline]
+    pub fn begin_object<W: JSONWrite>(&mut self, w: &mut W) -> Result<(), W::Error> {
+        self.depth += 1;
+        w.write_all(b"{")
+    }
+
+    /// Called before each key of an object
+    #[inline]
+    pub fn begin_object_key<W: JSONWrite>(
+        &mut self,
+        w: &mut W,
+        first: bool,
+    ) -> Result<(), W::Error> {
+        if !first {
+            w.write_all(b",")?;
+        }
+        self.write_line_start(w)
+    }
+
+    /// Called between a key and its value
+    #[inline]
+    pub fn begin_object_value<W: JSONWrite>(&mut self, w: &mut W) -> Result<(), W::Error> {
+        w.write_all(if self.space_after_colon { b": " } else { b":" })
+    }
+
+    /// Called after the last member of an object.
+    /// `empty` is true if the object had no member at all.
+    #[inline]
+    pub fn end_object<W: JSONWrite>(&mut self, w: &mut W, empty: bool) -> Result<(), W::Error> {
+        self.depth -= 1;
+        if !empty {
+            self.write_line_start(w)?;
+        }
+        w.write_all(b"}")
+    }
+
+    /// Called before the first element of a list
+    #[inline]
+    pub fn begin_array<W: JSONWrite>(&mut self, w: &mut W) -> Result<(), W::Error> {
+        self.depth += 1;
+        w.write_all(b"[")
+    }
+
+    /// Called before each element of a list
+    #[inline]
+    pub fn begin_array_value<W: JSONWrite>(
+        &mut self,
+        w: &mut W,
+        first: bool,
+    ) -> Result<(), W::Error> {
+        if !first {
+            w.write_all(b",")?;
+        }
+        self.write_line_start(w)
+    }
+
+    /// Called after the last element of a list.
+    /// `empty` is true if the list had no element at all.
+    #[inline]
+    pub fn end_array<W: JSONWrite>(&mut self, w: &mut W, empty: bool) -> Result<(), W::Error> {
+        self.depth -= 1;
+        if !empty {
+            self.write_line_start(w)?;
+        }
+        w.write_all(b"]")
+    }
+
+    /// Whether an integer of a type of the given `size` in bytes,
+    /// and of the given absolute value, has to be written as a string
+    #[inline]
+    pub fn quotes_integer(&self, size: usize, abs: u128) -> bool {
+        match self.integer_quoting {
+            IntegerQuoting::Never => false,
+            IntegerQuoting::OutsideSafeRange => abs > MAX_SAFE_INTEGER,
+            IntegerQuoting::Wide => size > 4,
+        }
+    }
+
+    /// Called to write a string value or key, including its surrounding quotes
+    #[inline]
+    pub fn write_string<W: JSONWrite>(&mut self, w: &mut W, s: &str) -> Result<(), W::Error> {
+        w.write_all(b"\"")?;
+        write_escaped(s, self.string_escaping, w)?;
+        w.write_all(b"\"")
+    }
+
+    /// Called to write a float that is NaN or infinite
+    pub fn write_non_finite_float<W: JSONWrite>(
+        &mut self,
+        w: &mut W,
+        value: f64,
+    ) -> Result<(), W::Error> {
+        let name: &[u8] = if value.is_nan() {
+            b"NaN"
+        } else if value > 0. {
+            b"Infinity"
+        } else {
+            b"-Infinity"
+        };
+        match self.non_finite_floats {
+            NonFiniteFloats::Null => w.write_all(b"null"),
+            NonFiniteFloats::Error => Err(JSONError::NonFiniteFloat.into()),
+            NonFiniteFloats::String => {
+                w.write_all(b"\"")?;
+                w.write_all(name)?;
+                w.write_all(b"\"")
+            }
+            NonFiniteFloats::Bare => w.write_all(name),
+        }
+    }
+}
+
+impl Default for JSONFormatter {
+    fn default() -> Self {
+        JSONFormatter::compact()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+    use crate::object::ToJSONObject;
+    use crate::*;
+
+    fn format<T: JSONValue>(value: T, mut formatter: JSONFormatter) -> String {
+        let mut buffer = vec![];
+        value
+            .write_json_formatted(&mut buffer, &mut formatter)
+            .unwrap();
+        String::from_utf8(buffer).unwrap()
+    }
+
+    #[test]
+    fn test_compact() {
+        let obj = json_object! { x: json_list![1, 2], y: vec![json_object!{}] };
+        assert_eq!(obj.to_json_string(), format(&obj, JSONFormatter::compact()));
+    }
+
+    #[test]
+    fn test_pretty() {
+        let obj = json_object! { x: json_list![1, json_list![]], y: vec![json_object!{}] };
+        assert_eq!(
+            "{\n  \"x\": [\n    1,\n    []\n  ],\n  \"y\": [\n    {}\n  ]\n}",
+            format(&obj, JSONFormatter::pretty())
+        );
+    }
+
+    #[test]
+    fn test_custom() {
+        let formatter = JSONFormatter::pretty()
+            .indent("\t")
+            .newline("\r\n")
+            .space_after_colon(false);
+        assert_eq!(
+            "{\r\n\t\"a\":[\r\n\t\t1\r\n\t]\r\n}",
+            format(json_object! { a: vec![1] }, formatter)
+        );
+    }
+
+    #[test]
+    fn test_non_finite_floats() {
+        let obj =
+            json_object! { nan: f64::NAN, inf: Some(f32::INFINITY), minus: vec![-f64::INFINITY] };
+        let with = |policy| JSONFormatter::compact().non_finite_floats(policy);
+        assert_eq!(
+            r#"{"nan":null,"inf":null,"minus":[null]}"#,
+            format(&obj, with(NonFiniteFloats::Null))
+        );
+        assert_eq!(
+            r#"{"nan":"NaN","inf":"Infinity","minus":["-Infinity"]}"#,
+            format(&obj, with(NonFiniteFloats::String))
+        );
+        assert_eq!(
+            r#"{"nan":NaN,"inf":Infinity,"minus":[-Infinity]}"#,
+            format(&obj, with(NonFiniteFloats::Bare))
+        );
+        let result = obj.write_json_formatted(&mut vec![], &mut with(NonFiniteFloats::Error));
+        #[cfg(feature = "std")]
+        assert_eq!(std::io::ErrorKind::InvalidData, result.unwrap_err().kind());
+        #[cfg(not(feature = "std"))]
+        assert_eq!(Err(JSONError::NonFiniteFloat), result);
+        assert_eq!(
+            r#"{"finite":1.5}"#,
+            format(json_object! { finite: 1.5 }, with(NonFiniteFloats::Error))
+        );
+    }
+
+    #[test]
+    fn test_integer_quoting() {
+        let safe = MAX_SAFE_INTEGER as i64;
+        let obj = json_list![safe, -safe, safe + 1, -safe - 1, 1u32, 1u64, u128::MAX];
+        let with = |quoting| JSONFormatter::compact().integer_quoting(quoting);
+        assert_eq!(
+            obj.to_json_string(),
+            format(obj, with(IntegerQuoting::Never))
+        );
+        assert_eq!(
+            r#"[9007199254740991,-9007199254740991,"9007199254740992","-9007199254740992",1,1,"340282366920938463463374607431768211455"]"#,
+            format(obj, with(IntegerQuoting::OutsideSafeRange))
+        );
+        assert_eq!(
+            r#"["9007199254740991","-9007199254740991","9007199254740992","-9007199254740992",1,"1","340282366920938463463374607431768211455"]"#,
+            format(obj, with(IntegerQuoting::Wide))
+        );
+    }
+
+    #[test]
+    fn test_string_escaping() {
+        let obj = json_object! { café: "☕", [String::from("thé")]: '🍵' };
+        let ascii = JSONFormatter::compact().string_escaping(StringEscaping::Ascii);
+        assert_eq!(
+            r#"{"caf\u00e9":"\u2615","th\u00e9":"\ud83c\udf75"}"#,
+            format(&obj, ascii)
+        );
+        let html = JSONFormatter::compact().string_escaping(StringEscaping::Html);
+        assert_eq!(obj.to_json_string(), format(&obj, html));
+        let minimal = JSONFormatter::compact().string_escaping(StringEscaping::Minimal);
+        assert_eq!(obj.to_json_string(), format(&obj, minimal));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_canonical_key_order() {
+        // The example of RFC 8785, section 3.2.3
+        let keys = ["€", "\r", "\u{fb33}", "1", "😀", "\u{80}", "ö"];
+        let map: std::collections::HashMap<_, _> = keys.iter().map(|&k| (k, k.len())).collect();
+        assert_eq!(
+            "{\"\\r\":1,\"1\":1,\"\u{80}\":2,\"ö\":2,\"€\":3,\"😀\":4,\"\u{fb33}\":3}",
+            format(&map, JSONFormatter::canonical())
+        );
+    }
+
+    #[test]
+    #[allow(clippy::excessive_precision)]
+    fn test_canonical_values() {
+        // The example of RFC 8785, section 3.2.2
+        let obj = json_object! {
+            numbers: json_list![333333333.33333329, 1E30, 4.50, 2e-3, 0.000000000000000000000000001],
+            string: "\u{20ac}$\u{f}\nA'B\"\\\\\"/",
+            literals: json_list![null, true, false]
+        };
+        assert_eq!(
+            r#"{"literals":[null,true,false],"numbers":[333333333.3333333,1e+30,4.5,0.002,1e-27],"string":"€$\u000f\nA'B\"\\\\\"/"}"#,
+            format(obj, JSONFormatter::canonical())
+        );
+        let large = json_list![-0.0, 1u64 << 53, (1u64 << 53) - 1, "\0\x7f"];
+        assert_eq!(
+            "[0,9007199254740992,9007199254740991,\"\\u0000\x7f\"]",
+            format(large, JSONFormatter::canonical())
+        );
+        let quoting = JSONFormatter::canonical().integer_quoting(IntegerQuoting::OutsideSafeRange);
+        assert_eq!(
+            "[0,\"9007199254740992\",9007199254740991,\"\\u0000\x7f\"]",
+            format(large, quoting)
+        );
+        let mut buffer = vec![];
+        let result = f64::NAN.write_json_formatted(&mut buffer, &mut JSONFormatter::canonical());
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_sort_keys() {
+        let obj = json_object! {
+            b: json_list![json_object! { y: 1, x: 2 }],
+            [String::from("a")]: json_object! {}
+        };
+        assert_eq!(
+            "{\n  \"a\": {},\n  \"b\": [\n    {\n      \"x\": 2,\n      \"y\": 1\n    }\n  ]\n}",
+            format(&obj, JSONFormatter::pretty().sort_keys(true))
+        );
+        let list = ToJSONObject(vec![("b", 1), ("a", 2), ("a", 3)]);
+        assert_eq!(
+            r#"{"a":2,"a":3,"b":1}"#,
+            format(&list, JSONFormatter::compact().sort_keys(true))
+        );
+    }
+
+    #[test]
+    fn test_compact_with_spaces() {
+        let formatter = JSONFormatter::compact().space_after_colon(true);
+        assert_eq!(
+            r#"{"a": 1,"b": [true]}"#,
+            format(json_object! { a: 1, b: json_list![true] }, formatter)
+        );
+    }
+}
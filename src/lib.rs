@@ -9,24 +9,78 @@
 //! with a number of properties that is known at compile-time can be efficiently
 //! stored in an ad-hoc type, that will have a compact representation in memory,
 //! and will be serialized much faster than a HashMap.
+//! The parts of such an object that are known at compile time, such as its keys,
+//! are even serialized at compile time (see the [`constant`](constant/index.html) module).
 //!
 //! # How to use
 //! This crate has two main macros, [`json_object!`](macro.json_object.html),
 //! and [`json_list!`](macro.json_list.html).
 //! Use them to create [json values](trait.JSONValue.html), that you can then serialize.
 //!
+//! For data whose shape is only known at runtime, the [`value!`](macro.value.html) macro
+//! creates a dynamic [`Value`](value/enum.Value.html).
+//!
+//! Values of different types can be stored together as
+//! [`dyn DynJSONValue`](dynamic/trait.DynJSONValue.html) trait objects.
+//! Documents that are easier to produce with a sequence of calls than as a value
+//! can be written with a [`JSONStreamWriter`](stream/struct.JSONStreamWriter.html).
+//! Sequences of documents can be written as JSON Lines or RFC 7464 records
+//! with the [`seq`](seq/index.html) module, and as server-sent events
+//! with the [`sse`](sse/index.html) module.
+//!
+//! # `no_std` support
+//! The crate can be used without the standard library by disabling the default `std` feature.
+//! JSON is then written to a [`JSONWrite`](write/trait.JSONWrite.html) that you implement,
+//! or to any [`core::fmt::Write`](write/struct.FmtWriter.html).
+//! The `alloc` feature adds back support for `Vec`, `String`, `Box` and the `to_json_*` methods.
+//!
+//! # Asynchronous serialization
+//! The `tokio` feature enables the [`async_io`](async_io/index.html) module,
+//! that writes values and streams of values to a tokio `AsyncWrite`.
+//!
+//! # Maps
+//! `HashMap`, `BTreeMap`, and with the `indexmap` feature, `IndexMap`, are serialized as objects.
+//! Wrap a `HashMap` in [`Sorted`](object/struct.Sorted.html) to serialize its keys in a deterministic order.
+//!
 //!
 
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+#[cfg(feature = "alloc")]
+use alloc::{boxed::Box, rc::Rc, string::String, sync::Arc, vec::Vec};
+
+#[cfg(feature = "tokio")]
+pub mod async_io;
 pub mod base_types;
+pub mod constant;
+pub mod dynamic;
+pub mod error;
+pub mod format;
 pub mod list;
 pub mod object;
+#[cfg(feature = "std")]
+pub mod read;
+#[cfg(feature = "std")]
+pub mod seq;
+#[cfg(feature = "alloc")]
+pub mod sse;
+#[cfg(feature = "alloc")]
+pub mod stream;
 pub mod string;
 pub mod utils;
+#[cfg(feature = "alloc")]
+pub mod value;
+pub mod write;
 
-use std::fmt;
-use std::fmt::Display;
-use std::fmt::Formatter;
-use std::io;
+use core::fmt;
+use core::fmt::Display;
+use core::fmt::Formatter;
+use format::JSONFormatter;
+use write::FmtWriter;
+use write::JSONWrite;
 
 /// A trait implemented by types that can be serialized to JSON
 ///
@@ -45,7 +99,57 @@ pub trait JSONValue {
     ///
     /// vec![1, 2, 3].write_json(&mut my_file);
     /// ```
-    fn write_json<W: io::Write>(&self, w: &mut W) -> io::Result<()>;
+    fn write_json<W: JSONWrite>(&self, w: &mut W) -> Result<(), W::Error>;
+
+    /// Write the object as json to the given writer,
+    /// using a [formatter](format/struct.JSONFormatter.html) to control the layout.
+    ///
+    /// The default implementation ignores the formatter and calls
+    /// [`write_json`](#tymethod.write_json), which is correct for values
+    /// that are neither objects nor lists.
+    ///
+    /// # Examples
+    ///
+    /// Write a pretty-printed JSON list
+    ///
+    /// ```
+    /// use json_in_type::JSONValue;
+    /// use json_in_type::format::JSONFormatter;
+    ///
+    /// let mut buffer: Vec<u8> = vec![];
+    /// vec![1, 2].write_json_formatted(&mut buffer, &mut JSONFormatter::pretty());
+    /// assert_eq!(b"[\n  1,\n  2\n]".to_vec(), buffer);
+    /// ```
+    #[inline]
+    fn write_json_formatted<W: JSONWrite>(
+        &self,
+        w: &mut W,
+        _f: &mut JSONFormatter,
+    ) -> Result<(), W::Error> {
+        self.write_json(w)
+    }
+
+    /// Returns the exact number of bytes that [`write_json`](#tymethod.write_json)
+    /// will write, or `None` if it cannot be known in advance.
+    ///
+    /// This is useful to set a `Content-Length` header before sending a value,
+    /// and is used to allocate buffers of the right size in
+    /// [`to_json_buffer`](#method.to_json_buffer).
+    /// The length cannot be known without consuming streaming values such as
+    /// iterators, so they return `None`, and so does the default implementation.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use json_in_type::*;
+    ///
+    /// let obj = json_object! { x: "hello", list: json_list![-1, true] };
+    /// assert_eq!(Some(r#"{"x":"hello","list":[-1,true]}"#.len()), obj.json_len());
+    /// ```
+    #[inline]
+    fn json_len(&self) -> Option<usize> {
+        None
+    }
 
     /// Returns the object formatted as a json string
     ///
@@ -53,29 +157,110 @@ pub trait JSONValue {
     /// If you implement JSONValue on your own types and emit invalid UTF-8
     /// in write_json. If you use the implementations of JSONValue provided
     /// in this library, this function will never panic.
+    #[cfg(feature = "alloc")]
     fn to_json_string(&self) -> String {
         // This is safe because the bytes we emit are all valid UTF-8
         String::from_utf8(self.to_json_buffer()).unwrap()
     }
 
     /// Returns a buffer containing the bytes of a json representation of the object
+    #[cfg(feature = "alloc")]
     fn to_json_buffer(&self) -> Vec<u8> {
-        let mut buffer = Vec::with_capacity(512);
+        let mut buffer = buffer_for(self);
         self.write_json(&mut buffer).unwrap();
         buffer
     }
+
+    /// Returns the object formatted as an indented, human-readable json string
+    #[cfg(feature = "alloc")]
+    fn to_json_string_pretty(&self) -> String {
+        // The pretty output is at least as long as the compact one
+        let mut buffer = buffer_for(self);
+        self.write_json_formatted(&mut buffer, &mut JSONFormatter::pretty())
+            .unwrap();
+        String::from_utf8(buffer).unwrap()
+    }
+}
+
+/// An empty buffer, with room for the compact serialization of the value if its length is known
+#[cfg(feature = "alloc")]
+fn buffer_for<T: JSONValue + ?Sized>(value: &T) -> Vec<u8> {
+    Vec::with_capacity(value.json_len().unwrap_or(512))
 }
 
-impl<'a, S: JSONValue + ?Sized> JSONValue for &'a S {
-    fn write_json<W: io::Write>(&self, w: &mut W) -> io::Result<()> {
+impl<S: JSONValue + ?Sized> JSONValue for &S {
+    fn write_json<W: JSONWrite>(&self, w: &mut W) -> Result<(), W::Error> {
         (**self).write_json(w)
     }
+
+    fn json_len(&self) -> Option<usize> {
+        (**self).json_len()
+    }
+
+    fn write_json_formatted<W: JSONWrite>(
+        &self,
+        w: &mut W,
+        f: &mut JSONFormatter,
+    ) -> Result<(), W::Error> {
+        (**self).write_json_formatted(w, f)
+    }
 }
 
-impl<'a, S: JSONValue + ?Sized> JSONValue for Box<S> {
-    fn write_json<W: io::Write>(&self, w: &mut W) -> io::Result<()> {
+#[cfg(feature = "alloc")]
+impl<S: JSONValue + ?Sized> JSONValue for Box<S> {
+    fn write_json<W: JSONWrite>(&self, w: &mut W) -> Result<(), W::Error> {
         (**self).write_json(w)
     }
+
+    fn json_len(&self) -> Option<usize> {
+        (**self).json_len()
+    }
+
+    fn write_json_formatted<W: JSONWrite>(
+        &self,
+        w: &mut W,
+        f: &mut JSONFormatter,
+    ) -> Result<(), W::Error> {
+        (**self).write_json_formatted(w, f)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<S: JSONValue + ?Sized> JSONValue for Rc<S> {
+    fn write_json<W: JSONWrite>(&self, w: &mut W) -> Result<(), W::Error> {
+        (**self).write_json(w)
+    }
+
+    fn json_len(&self) -> Option<usize> {
+        (**self).json_len()
+    }
+
+    fn write_json_formatted<W: JSONWrite>(
+        &self,
+        w: &mut W,
+        f: &mut JSONFormatter,
+    ) -> Result<(), W::Error> {
+        (**self).write_json_formatted(w, f)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<S: JSONValue + ?Sized> JSONValue for Arc<S> {
+    fn write_json<W: JSONWrite>(&self, w: &mut W) -> Result<(), W::Error> {
+        (**self).write_json(w)
+    }
+
+    fn json_len(&self) -> Option<usize> {
+        (**self).json_len()
+    }
+
+    fn write_json_formatted<W: JSONWrite>(
+        &self,
+        w: &mut W,
+        f: &mut JSONFormatter,
+    ) -> Result<(), W::Error> {
+        (**self).write_json_formatted(w, f)
+    }
 }
 
 /// Encapsulates a [JSONValue](trait.JSONValue.html) and implements useful traits.
@@ -90,6 +275,9 @@ impl<'a, S: JSONValue + ?Sized> JSONValue for Box<S> {
 ///
 /// println!("{}", x_json); // just works. Displays [null,null,null]
 ///
+/// // The alternate flag pretty-prints the value
+/// assert_eq!("[\n  null,\n  null,\n  null\n]", format!("{:#}", x_json));
+///
 /// let my_buffer : Vec<u8> = x_json.into();
 /// assert_eq!(b"[null,null,null]".to_vec(), my_buffer);
 /// ```
@@ -97,16 +285,183 @@ pub struct JSON<T: JSONValue>(pub T);
 
 impl<T: JSONValue> Display for JSON<T> {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
-        let mut writer = utils::FormatterWriter(f);
-        self.0
-            .write_json(&mut writer)
-            .map(|_size| ())
-            .map_err(|_err| fmt::Error {})
+        let pretty = f.alternate();
+        let mut writer = FmtWriter(f);
+        if pretty {
+            self.0
+                .write_json_formatted(&mut writer, &mut JSONFormatter::pretty())
+        } else {
+            self.0.write_json(&mut writer)
+        }
     }
 }
 
+#[cfg(feature = "alloc")]
 impl<T: JSONValue> From<JSON<T>> for Vec<u8> {
     fn from(json: JSON<T>) -> Self {
         json.0.to_json_buffer()
     }
 }
+
+/// Creates a static json value from JSON-like syntax.
+///
+/// Objects and lists can be nested freely, and expand to the same zero-sized,
+/// inlined types as [`json_object!`](macro.json_object.html) and
+/// [`json_list!`](macro.json_list.html).
+/// Object keys are string literals, escaped at compile time, or Rust expressions
+/// in square brackets. Values are `null`, `true`, `false`, nested objects and lists,
+/// or any Rust expression whose type implements [`JSONValue`](trait.JSONValue.html).
+///
+/// # Examples
+///
+/// ```
+/// use json_in_type::*;
+///
+/// let name = "Ada";
+/// let tags = vec!["math", "code"];
+/// let user = json!({
+///     "name": name,
+///     "content-type": "person",
+///     "born": 1815,
+///     "tags": tags,
+///     "links": [{"rel": "self", "href": ["/users/", name].concat()}, null],
+///     "admin": false,
+///     [name.to_lowercase()]: true,
+/// });
+///
+/// assert_eq!(
+///     r#"{"name":"Ada","content-type":"person","born":1815,"tags":["math","code"],"links":[{"rel":"self","href":"/users/Ada"},null],"admin":false,"ada":true}"#,
+///     user.to_json_string()
+/// );
+/// ```
+///
+/// Values that are known at compile time take no space in memory:
+///
+/// ```
+/// use json_in_type::*;
+///
+/// let constant = json!({"nested": [null, {"ok": true}, []], "list": [false]});
+/// assert_eq!(0, std::mem::size_of_val(&constant));
+/// ```
+#[macro_export]
+macro_rules! json {
+    (null) => { () };
+    (true) => { $crate::base_types::JSONtrue };
+    (false) => { $crate::base_types::JSONfalse };
+    ([ $($list:tt)* ]) => { json!(@list [] $($list)*) };
+    ({ $($object:tt)* }) => { json!(@object [] $($object)*) };
+    ($value:expr) => { $value };
+
+    // The elements of a list, one at a time.
+    // Consecutive constant elements are accumulated, and serialized together at compile time.
+    (@list []) => { $crate::list::JSONListEnd{} };
+    (@list [$($elems:tt)+]) => { inlined_json_fragment!(list: [$($elems)+]) };
+    (@list [$($elems:tt)*] null $(, $($rest:tt)*)?) => {
+        json!(@list [$($elems)* (())] $($($rest)*)?)
+    };
+    (@list [$($elems:tt)*] true $(, $($rest:tt)*)?) => {
+        json!(@list [$($elems)* ($crate::base_types::JSONtrue)] $($($rest)*)?)
+    };
+    (@list [$($elems:tt)*] false $(, $($rest:tt)*)?) => {
+        json!(@list [$($elems)* ($crate::base_types::JSONfalse)] $($($rest)*)?)
+    };
+    (@list [$($elems:tt)*] [ $($list:tt)* ] $(, $($rest:tt)*)?) => {
+        json!(@if_const [[ $($list)* ]]
+            { json!(@list [$($elems)* (json!([ $($list)* ]))] $($($rest)*)?) }
+            { json!(@list_elem [$($elems)*] json!([ $($list)* ]), $($($rest)*)?) })
+    };
+    (@list [$($elems:tt)*] { $($object:tt)* } $(, $($rest:tt)*)?) => {
+        json!(@if_const [{ $($object)* }]
+            { json!(@list [$($elems)* (json!({ $($object)* }))] $($($rest)*)?) }
+            { json!(@list_elem [$($elems)*] json!({ $($object)* }), $($($rest)*)?) })
+    };
+    (@list [$($elems:tt)*] $elem:expr $(, $($rest:tt)*)?) => {
+        json!(@list_elem [$($elems)*] $elem, $($($rest)*)?)
+    };
+    (@list_elem [] $elem:expr, $($rest:tt)*) => {
+        $crate::list::JSONListElem::new($elem, json!(@list [] $($rest)*))
+    };
+    (@list_elem [$($elems:tt)+] $elem:expr, $($rest:tt)*) => {
+        inlined_json_fragment!(list: [$($elems)+], next: json!(@list_elem [] $elem, $($rest)*))
+    };
+
+    // The members of an object, one at a time.
+    // Consecutive constant members are accumulated, and serialized together at compile time.
+    (@object []) => { $crate::object::JSONObjectEnd{} };
+    (@object [$($members:tt)+]) => { inlined_json_fragment!(object: [$($members)+]) };
+    (@object [$($members:tt)*] $key:literal : null $(, $($rest:tt)*)?) => {
+        json!(@object [$($members)* ($key, ())] $($($rest)*)?)
+    };
+    (@object [$($members:tt)*] $key:literal : true $(, $($rest:tt)*)?) => {
+        json!(@object [$($members)* ($key, $crate::base_types::JSONtrue)] $($($rest)*)?)
+    };
+    (@object [$($members:tt)*] $key:literal : false $(, $($rest:tt)*)?) => {
+        json!(@object [$($members)* ($key, $crate::base_types::JSONfalse)] $($($rest)*)?)
+    };
+    (@object [$($members:tt)*] $key:literal : [ $($list:tt)* ] $(, $($rest:tt)*)?) => {
+        json!(@if_const [[ $($list)* ]]
+            { json!(@object [$($members)* ($key, json!([ $($list)* ]))] $($($rest)*)?) }
+            { json!(@member [$($members)*] $key, json!([ $($list)* ]), $($($rest)*)?) })
+    };
+    (@object [$($members:tt)*] $key:literal : { $($object:tt)* } $(, $($rest:tt)*)?) => {
+        json!(@if_const [{ $($object)* }]
+            { json!(@object [$($members)* ($key, json!({ $($object)* }))] $($($rest)*)?) }
+            { json!(@member [$($members)*] $key, json!({ $($object)* }), $($($rest)*)?) })
+    };
+    // Computed keys are never constant
+    (@object [$($members:tt)*] $key:tt : null $(, $($rest:tt)*)?) => {
+        json!(@member [$($members)*] $key, json!(null), $($($rest)*)?)
+    };
+    (@object [$($members:tt)*] $key:tt : true $(, $($rest:tt)*)?) => {
+        json!(@member [$($members)*] $key, json!(true), $($($rest)*)?)
+    };
+    (@object [$($members:tt)*] $key:tt : false $(, $($rest:tt)*)?) => {
+        json!(@member [$($members)*] $key, json!(false), $($($rest)*)?)
+    };
+    (@object [$($members:tt)*] $key:tt : [ $($list:tt)* ] $(, $($rest:tt)*)?) => {
+        json!(@member [$($members)*] $key, json!([ $($list)* ]), $($($rest)*)?)
+    };
+    (@object [$($members:tt)*] $key:tt : { $($object:tt)* } $(, $($rest:tt)*)?) => {
+        json!(@member [$($members)*] $key, json!({ $($object)* }), $($($rest)*)?)
+    };
+    (@object [$($members:tt)*] $key:tt : $value:expr $(, $($rest:tt)*)?) => {
+        json!(@member [$($members)*] $key, $value, $($($rest)*)?)
+    };
+    (@member [$($members:tt)+] $key:tt, $value:expr, $($rest:tt)*) => {
+        inlined_json_fragment!(object: [$($members)+], next: json!(@member [] $key, $value, $($rest)*))
+    };
+    (@member [] [ $key:expr ], $value:expr, $($rest:tt)*) => {
+        $crate::object::JSONObjectEntry {
+            key: $key,
+            value: $value,
+            next: json!(@object [] $($rest)*),
+        }
+    };
+    (@member [] $key:literal, $value:expr, $($rest:tt)*) => {
+        inlined_json_object! {
+            key_str: $key,
+            value: $value,
+            next: json!(@object [] $($rest)*)
+        }
+    };
+
+    // Expands to the first block if the given tokens only contain constant values,
+    // and to the second one otherwise
+    (@if_const [] { $($then:tt)* } $else:tt) => { $($then)* };
+    (@if_const [$key:tt : null , $($rest:tt)*] $then:tt $else:tt) => { json!(@if_const [$($rest)*] $then $else) };
+    (@if_const [$key:tt : true , $($rest:tt)*] $then:tt $else:tt) => { json!(@if_const [$($rest)*] $then $else) };
+    (@if_const [$key:tt : false , $($rest:tt)*] $then:tt $else:tt) => { json!(@if_const [$($rest)*] $then $else) };
+    (@if_const [null $(, $($rest:tt)*)?] $then:tt $else:tt) => { json!(@if_const [$($($rest)*)?] $then $else) };
+    (@if_const [true $(, $($rest:tt)*)?] $then:tt $else:tt) => { json!(@if_const [$($($rest)*)?] $then $else) };
+    (@if_const [false $(, $($rest:tt)*)?] $then:tt $else:tt) => { json!(@if_const [$($($rest)*)?] $then $else) };
+    (@if_const [, $($rest:tt)*] $then:tt $else:tt) => { json!(@if_const [$($rest)*] $then $else) };
+    (@if_const [[ $($key:tt)* ] : $($rest:tt)*] $then:tt { $($else:tt)* }) => { $($else)* };
+    (@if_const [$key:literal : $($rest:tt)*] $then:tt $else:tt) => { json!(@if_const [$($rest)*] $then $else) };
+    (@if_const [{ $($object:tt)* } $($rest:tt)*] $then:tt $else:tt) => {
+        json!(@if_const [$($object)* $($rest)*] $then $else)
+    };
+    (@if_const [[ $($list:tt)* ] $($rest:tt)*] $then:tt $else:tt) => {
+        json!(@if_const [$($list)* $($rest)*] $then $else)
+    };
+    (@if_const [$($other:tt)*] $then:tt { $($else:tt)* }) => { $($else)* };
+}
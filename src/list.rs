@@ -1,15 +1,19 @@
 //! Serialization to JSON lists like `[0,true,"x"]`
 
+use super::constant::{ConstFragment, ConstJSONValue};
+use super::format::JSONFormatter;
+use super::write::JSONWrite;
 use super::JSONValue;
-use std::cell::RefCell;
-use std::io;
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+use core::cell::RefCell;
 
 #[inline(always)]
-fn write_json_iterator<J, I, W>(iter: &mut I, w: &mut W) -> io::Result<()>
+fn write_json_iterator<J, I, W>(iter: &mut I, w: &mut W) -> Result<(), W::Error>
 where
     I: Iterator<Item = J>,
     J: JSONValue,
-    W: io::Write,
+    W: JSONWrite,
 {
     w.write_all(b"[")?;
     if let Some(first) = iter.next() {
@@ -22,11 +26,60 @@ where
     w.write_all(b"]")
 }
 
+fn write_json_iterator_formatted<J, I, W>(
+    iter: &mut I,
+    w: &mut W,
+    f: &mut JSONFormatter,
+) -> Result<(), W::Error>
+where
+    I: Iterator<Item = J>,
+    J: JSONValue,
+    W: JSONWrite,
+{
+    f.begin_array(w)?;
+    let mut empty = true;
+    for x in iter {
+        f.begin_array_value(w, empty)?;
+        x.write_json_formatted(w, f)?;
+        empty = false;
+    }
+    f.end_array(w, empty)
+}
+
+/// Length of a json list containing the given values
+#[inline]
+fn json_len_iterator<J, I>(iter: I) -> Option<usize>
+where
+    I: Iterator<Item = J>,
+    J: JSONValue,
+{
+    let mut count = 0;
+    let mut len = 2; // [ and ]
+    for x in iter {
+        len += x.json_len()?;
+        count += 1;
+    }
+    Some(len + count.max(1) - 1) // commas
+}
+
+#[cfg(feature = "alloc")]
 impl<T: JSONValue> JSONValue for Vec<T> {
     #[inline(always)]
-    fn write_json<W: io::Write>(&self, w: &mut W) -> io::Result<()> {
+    fn write_json<W: JSONWrite>(&self, w: &mut W) -> Result<(), W::Error> {
         write_json_iterator(&mut self.iter(), w)
     }
+
+    fn write_json_formatted<W: JSONWrite>(
+        &self,
+        w: &mut W,
+        f: &mut JSONFormatter,
+    ) -> Result<(), W::Error> {
+        write_json_iterator_formatted(&mut self.iter(), w, f)
+    }
+
+    fn json_len(&self) -> Option<usize> {
+        json_len_iterator(self.iter())
+    }
 }
 
 /// Allows to serialize an iterator to JSON in a streaming fashion.
@@ -87,9 +140,17 @@ impl<T: JSONValue> JSONValue for Vec<T> {
 /// ```
 impl<T: JSONValue, I: Iterator<Item = T>> JSONValue for RefCell<I> {
     #[inline]
-    fn write_json<W: io::Write>(&self, w: &mut W) -> io::Result<()> {
+    fn write_json<W: JSONWrite>(&self, w: &mut W) -> Result<(), W::Error> {
         write_json_iterator(&mut *self.borrow_mut(), w)
     }
+
+    fn write_json_formatted<W: JSONWrite>(
+        &self,
+        w: &mut W,
+        f: &mut JSONFormatter,
+    ) -> Result<(), W::Error> {
+        write_json_iterator_formatted(&mut *self.borrow_mut(), w, f)
+    }
 }
 
 /// A struct used to wrap another type and make it serializable as a json list.
@@ -114,55 +175,437 @@ impl<T: JSONValue, U> JSONValue for ToJSONList<T, U>
 where
     for<'a> &'a U: IntoIterator<Item = &'a T>,
 {
-    fn write_json<W: io::Write>(&self, w: &mut W) -> io::Result<()> {
+    fn write_json<W: JSONWrite>(&self, w: &mut W) -> Result<(), W::Error> {
         write_json_iterator(&mut (&self.0).into_iter(), w)
     }
+
+    fn write_json_formatted<W: JSONWrite>(
+        &self,
+        w: &mut W,
+        f: &mut JSONFormatter,
+    ) -> Result<(), W::Error> {
+        write_json_iterator_formatted(&mut (&self.0).into_iter(), w, f)
+    }
+
+    fn json_len(&self) -> Option<usize> {
+        json_len_iterator((&self.0).into_iter())
+    }
+}
+
+/// Implemented by the static lists created by [`json_list!`](../macro.json_list.html).
+pub trait JSONList: JSONValue + ListElements {
+    fn write_json_ending<W: JSONWrite>(&self, w: &mut W) -> Result<(), W::Error>;
+    fn write_json_ending_formatted<W: JSONWrite>(
+        &self,
+        w: &mut W,
+        f: &mut JSONFormatter,
+    ) -> Result<(), W::Error>;
+    fn json_len_ending(&self) -> Option<usize>;
+
+    /// Returns a list with the elements of this list, followed by `value`.
+    ///
+    /// Lists can be built step by step, across functions,
+    /// and the result is still a static structure whose type stores the shape of the list.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use json_in_type::*;
+    /// use json_in_type::list::JSONList;
+    ///
+    /// let list = json_list![1, true].push("x").push(());
+    /// assert_eq!(r#"[1,true,"x",null]"#, list.to_json_string());
+    /// ```
+    fn push<V: JSONValue>(self, value: V) -> JSONListAppend<Self, JSONListElem<V, JSONListEnd>>
+    where
+        Self: Sized,
+    {
+        self.append(JSONListElem::new(value, JSONListEnd))
+    }
+
+    /// Returns a list with the elements of this list, followed by the elements of `other`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use json_in_type::*;
+    /// use json_in_type::list::JSONList;
+    ///
+    /// let list = json_list![1, 2].append(json_list![]).append(json_list![3]);
+    /// assert_eq!("[1,2,3]", list.to_json_string());
+    /// ```
+    fn append<L: JSONList>(self, other: L) -> JSONListAppend<Self, L>
+    where
+        Self: Sized,
+    {
+        JSONListAppend { list: self, other }
+    }
 }
 
-pub trait JSONList: JSONValue {
-    fn write_json_ending<W: io::Write>(&self, w: &mut W) -> io::Result<()>;
+/// Gives access to the elements of a static list, without its brackets,
+/// to write them in another list.
+pub trait ListElements {
+    /// Writes the elements, the first one preceded by `[` if `first` is true,
+    /// and all the others by a comma.
+    /// Returns whether the list is still empty, that is, `first` when there are no elements.
+    fn write_elements<W: JSONWrite>(&self, w: &mut W, first: bool) -> Result<bool, W::Error>;
+
+    /// Same as [`write_elements`](#tymethod.write_elements), with the given formatter.
+    /// The list has to be opened with
+    /// [`begin_array`](../format/struct.JSONFormatter.html#method.begin_array) beforehand.
+    fn write_elements_formatted<W: JSONWrite>(
+        &self,
+        w: &mut W,
+        f: &mut JSONFormatter,
+        first: bool,
+    ) -> Result<bool, W::Error>;
+
+    /// The length of the elements, including the separator before each one
+    fn elements_len(&self) -> Option<usize>;
 }
 
+#[derive(Clone, Copy, Debug)]
 pub struct JSONListElem<T: JSONValue, U: JSONList> {
-    elem: T,
-    next: U,
+    pub elem: T,
+    pub next: U,
 }
 
 impl<T: JSONValue, U: JSONList> JSONListElem<T, U> {
-    pub fn new(elem: T, next: U) -> JSONListElem<T, U> {
+    pub const fn new(elem: T, next: U) -> JSONListElem<T, U> {
         JSONListElem { elem, next }
     }
 }
 
 impl<T: JSONValue, U: JSONList> JSONList for JSONListElem<T, U> {
     #[inline(always)]
-    fn write_json_ending<W: io::Write>(&self, w: &mut W) -> io::Result<()> {
+    fn write_json_ending<W: JSONWrite>(&self, w: &mut W) -> Result<(), W::Error> {
         w.write_all(b",")?;
         self.elem.write_json(w)?;
         self.next.write_json_ending(w)
     }
+
+    #[inline(always)]
+    fn write_json_ending_formatted<W: JSONWrite>(
+        &self,
+        w: &mut W,
+        f: &mut JSONFormatter,
+    ) -> Result<(), W::Error> {
+        f.begin_array_value(w, false)?;
+        self.elem.write_json_formatted(w, f)?;
+        self.next.write_json_ending_formatted(w, f)
+    }
+
+    #[inline]
+    fn json_len_ending(&self) -> Option<usize> {
+        Some(1 + self.elem.json_len()? + self.next.json_len_ending()?)
+    }
+}
+
+impl<T: JSONValue, U: JSONList> ListElements for JSONListElem<T, U> {
+    #[inline(always)]
+    fn write_elements<W: JSONWrite>(&self, w: &mut W, first: bool) -> Result<bool, W::Error> {
+        w.write_all(if first { b"[" } else { b"," })?;
+        self.elem.write_json(w)?;
+        self.next.write_elements(w, false)
+    }
+
+    fn write_elements_formatted<W: JSONWrite>(
+        &self,
+        w: &mut W,
+        f: &mut JSONFormatter,
+        first: bool,
+    ) -> Result<bool, W::Error> {
+        f.begin_array_value(w, first)?;
+        self.elem.write_json_formatted(w, f)?;
+        self.next.write_elements_formatted(w, f, false)
+    }
+
+    #[inline]
+    fn elements_len(&self) -> Option<usize> {
+        Some(1 + self.elem.json_len()? + self.next.elements_len()?)
+    }
 }
 
 impl<T: JSONValue, U: JSONList> JSONValue for JSONListElem<T, U> {
-    fn write_json<W: io::Write>(&self, w: &mut W) -> io::Result<()> {
+    fn write_json<W: JSONWrite>(&self, w: &mut W) -> Result<(), W::Error> {
         w.write_all(b"[")?;
         self.elem.write_json(w)?;
         self.next.write_json_ending(w)
     }
+
+    fn write_json_formatted<W: JSONWrite>(
+        &self,
+        w: &mut W,
+        f: &mut JSONFormatter,
+    ) -> Result<(), W::Error> {
+        f.begin_array(w)?;
+        f.begin_array_value(w, true)?;
+        self.elem.write_json_formatted(w, f)?;
+        self.next.write_json_ending_formatted(w, f)
+    }
+
+    fn json_len(&self) -> Option<usize> {
+        // The opening bracket takes the place of the comma
+        self.json_len_ending()
+    }
 }
 
+#[derive(Clone, Copy, Debug)]
 pub struct JSONListEnd;
 
 impl JSONList for JSONListEnd {
-    fn write_json_ending<W: io::Write>(&self, w: &mut W) -> io::Result<()> {
+    fn write_json_ending<W: JSONWrite>(&self, w: &mut W) -> Result<(), W::Error> {
         w.write_all(b"]")
     }
+
+    fn write_json_ending_formatted<W: JSONWrite>(
+        &self,
+        w: &mut W,
+        f: &mut JSONFormatter,
+    ) -> Result<(), W::Error> {
+        f.end_array(w, false)
+    }
+
+    #[inline]
+    fn json_len_ending(&self) -> Option<usize> {
+        Some(1)
+    }
+}
+
+impl ListElements for JSONListEnd {
+    #[inline(always)]
+    fn write_elements<W: JSONWrite>(&self, _w: &mut W, first: bool) -> Result<bool, W::Error> {
+        Ok(first)
+    }
+
+    fn write_elements_formatted<W: JSONWrite>(
+        &self,
+        _w: &mut W,
+        _f: &mut JSONFormatter,
+        first: bool,
+    ) -> Result<bool, W::Error> {
+        Ok(first)
+    }
+
+    #[inline]
+    fn elements_len(&self) -> Option<usize> {
+        Some(0)
+    }
 }
 
 impl JSONValue for JSONListEnd {
-    fn write_json<W: io::Write>(&self, w: &mut W) -> io::Result<()> {
+    fn write_json<W: JSONWrite>(&self, w: &mut W) -> Result<(), W::Error> {
         w.write_all(b"[]")
     }
+
+    fn write_json_formatted<W: JSONWrite>(
+        &self,
+        w: &mut W,
+        f: &mut JSONFormatter,
+    ) -> Result<(), W::Error> {
+        f.begin_array(w)?;
+        f.end_array(w, true)
+    }
+
+    #[inline]
+    fn json_len(&self) -> Option<usize> {
+        Some(2)
+    }
+}
+
+/// The elements of a list, followed by the elements of another list.
+/// It is created by [`JSONList::push`](trait.JSONList.html#method.push)
+/// and [`JSONList::append`](trait.JSONList.html#method.append).
+#[derive(Clone, Copy, Debug)]
+pub struct JSONListAppend<L: JSONList, M: JSONList> {
+    pub list: L,
+    pub other: M,
+}
+
+impl<L: JSONList, M: JSONList> JSONList for JSONListAppend<L, M> {
+    #[inline(always)]
+    fn write_json_ending<W: JSONWrite>(&self, w: &mut W) -> Result<(), W::Error> {
+        self.write_elements(w, false)?;
+        w.write_all(b"]")
+    }
+
+    #[inline(always)]
+    fn write_json_ending_formatted<W: JSONWrite>(
+        &self,
+        w: &mut W,
+        f: &mut JSONFormatter,
+    ) -> Result<(), W::Error> {
+        self.write_elements_formatted(w, f, false)?;
+        f.end_array(w, false)
+    }
+
+    #[inline]
+    fn json_len_ending(&self) -> Option<usize> {
+        Some(self.elements_len()? + 1)
+    }
+}
+
+impl<L: JSONList, M: JSONList> ListElements for JSONListAppend<L, M> {
+    #[inline(always)]
+    fn write_elements<W: JSONWrite>(&self, w: &mut W, first: bool) -> Result<bool, W::Error> {
+        let first = self.list.write_elements(w, first)?;
+        self.other.write_elements(w, first)
+    }
+
+    fn write_elements_formatted<W: JSONWrite>(
+        &self,
+        w: &mut W,
+        f: &mut JSONFormatter,
+        first: bool,
+    ) -> Result<bool, W::Error> {
+        let first = self.list.write_elements_formatted(w, f, first)?;
+        self.other.write_elements_formatted(w, f, first)
+    }
+
+    #[inline]
+    fn elements_len(&self) -> Option<usize> {
+        Some(self.list.elements_len()? + self.other.elements_len()?)
+    }
+}
+
+impl<L: JSONList, M: JSONList> JSONValue for JSONListAppend<L, M> {
+    fn write_json<W: JSONWrite>(&self, w: &mut W) -> Result<(), W::Error> {
+        let empty = self.write_elements(w, true)?;
+        w.write_all(if empty { b"[]" } else { b"]" })
+    }
+
+    fn write_json_formatted<W: JSONWrite>(
+        &self,
+        w: &mut W,
+        f: &mut JSONFormatter,
+    ) -> Result<(), W::Error> {
+        f.begin_array(w)?;
+        let empty = self.write_elements_formatted(w, f, true)?;
+        f.end_array(w, empty)
+    }
+
+    fn json_len(&self) -> Option<usize> {
+        // Each element takes at least two bytes with its separator
+        Some(self.elements_len()?.max(1) + 1)
+    }
+}
+
+/// Constant elements of a list, serialized at compile time, followed by other elements.
+/// It is created by [`json_list!`](../macro.json_list.html) and [`json!`](../macro.json.html)
+/// for consecutive elements that are known at compile time:
+/// they are written with a single call to `write_all`.
+/// The elements are only used for formatted output.
+#[doc(hidden)]
+#[derive(Clone, Copy, Debug)]
+pub struct JSONListFragment<F: ConstFragment, E: ListElements, U: JSONList> {
+    pub fragment: F,
+    pub elements: E,
+    pub next: U,
+}
+
+impl<F: ConstFragment, E: ListElements, U: JSONList> JSONListFragment<F, E, U> {
+    #[inline(always)]
+    fn write_fragment<W: JSONWrite>(&self, w: &mut W, fragment: &str) -> Result<(), W::Error> {
+        w.write_all(fragment.as_bytes())?;
+        if F::END {
+            Ok(())
+        } else {
+            self.next.write_json_ending(w)
+        }
+    }
+
+    #[inline]
+    fn fragment_len(&self) -> Option<usize> {
+        // The fragment has the same length with [ or ,
+        let len = F::FIRST.len();
+        if F::END {
+            Some(len)
+        } else {
+            Some(len + self.next.json_len_ending()?)
+        }
+    }
+}
+
+impl<F: ConstFragment, E: ListElements, U: JSONList> JSONList for JSONListFragment<F, E, U> {
+    #[inline(always)]
+    fn write_json_ending<W: JSONWrite>(&self, w: &mut W) -> Result<(), W::Error> {
+        self.write_fragment(w, F::NEXT)
+    }
+
+    #[inline(always)]
+    fn write_json_ending_formatted<W: JSONWrite>(
+        &self,
+        w: &mut W,
+        f: &mut JSONFormatter,
+    ) -> Result<(), W::Error> {
+        self.elements.write_elements_formatted(w, f, false)?;
+        self.next.write_json_ending_formatted(w, f)
+    }
+
+    #[inline]
+    fn json_len_ending(&self) -> Option<usize> {
+        self.fragment_len()
+    }
+}
+
+impl<F: ConstFragment, E: ListElements, U: JSONList> ListElements for JSONListFragment<F, E, U> {
+    #[inline(always)]
+    fn write_elements<W: JSONWrite>(&self, w: &mut W, first: bool) -> Result<bool, W::Error> {
+        let fragment = if first { F::FIRST } else { F::NEXT }.as_bytes();
+        if F::END {
+            // Without the closing bracket
+            w.write_all(&fragment[..fragment.len() - 1])?;
+            Ok(false)
+        } else {
+            w.write_all(fragment)?;
+            self.next.write_elements(w, false)
+        }
+    }
+
+    fn write_elements_formatted<W: JSONWrite>(
+        &self,
+        w: &mut W,
+        f: &mut JSONFormatter,
+        first: bool,
+    ) -> Result<bool, W::Error> {
+        self.elements.write_elements_formatted(w, f, first)?;
+        self.next.write_elements_formatted(w, f, false)
+    }
+
+    #[inline]
+    fn elements_len(&self) -> Option<usize> {
+        let len = F::FIRST.len();
+        if F::END {
+            Some(len - 1)
+        } else {
+            Some(len + self.next.elements_len()?)
+        }
+    }
+}
+
+impl<F: ConstFragment, E: ListElements, U: JSONList> JSONValue for JSONListFragment<F, E, U> {
+    fn write_json<W: JSONWrite>(&self, w: &mut W) -> Result<(), W::Error> {
+        self.write_fragment(w, F::FIRST)
+    }
+
+    fn write_json_formatted<W: JSONWrite>(
+        &self,
+        w: &mut W,
+        f: &mut JSONFormatter,
+    ) -> Result<(), W::Error> {
+        f.begin_array(w)?;
+        self.elements.write_elements_formatted(w, f, true)?;
+        self.next.write_json_ending_formatted(w, f)
+    }
+
+    fn json_len(&self) -> Option<usize> {
+        self.fragment_len()
+    }
+}
+
+/// A list made only of constant elements ends with a fragment that includes the closing bracket
+impl<F: ConstFragment, E: ListElements> ConstJSONValue for JSONListFragment<F, E, JSONListEnd> {
+    const JSON: &'static str = F::FIRST;
 }
 
 /// Creates a static json list that can be serialized very fast.
@@ -189,9 +632,46 @@ impl JSONValue for JSONListEnd {
 /// ```
 #[macro_export]
 macro_rules! json_list {
-    (null $($tt:tt)* ) => { json_list![() $($tt)*] };
-    (true $($tt:tt)* ) => { json_list![$crate::base_types::JSONtrue $($tt)*] };
-    (false $($tt:tt)* ) => { json_list![$crate::base_types::JSONfalse $($tt)*] };
+    // Consecutive constant elements are serialized together at compile time
+    (null $(, $($rest:tt)*)?) => { json_list!(@const [] null $(, $($rest)*)?) };
+    (true $(, $($rest:tt)*)?) => { json_list!(@const [] true $(, $($rest)*)?) };
+    (false $(, $($rest:tt)*)?) => { json_list!(@const [] false $(, $($rest)*)?) };
+    (json_object ! $body:tt $(, $($rest:tt)*)?) => { json_list!(@const [] json_object ! $body $(, $($rest)*)?) };
+    (json_list ! $body:tt $(, $($rest:tt)*)?) => { json_list!(@const [] json_list ! $body $(, $($rest)*)?) };
+    (@const [$($elems:tt)*] $value:tt $(! $body:tt)? $(, $($rest:tt)*)?) => {
+        json_list!(@const_value [$($elems)*] ($value $(! $body)?) $($($rest)*)?)
+    };
+    (@const [$($elems:tt)+]) => { inlined_json_fragment!(list: [$($elems)+]) };
+    (@const [$($elems:tt)+] $($rest:tt)+) => {
+        inlined_json_fragment!(list: [$($elems)+], next: json_list!($($rest)+))
+    };
+    (@const_value [$($elems:tt)*] (null) $($rest:tt)*) => {
+        json_list!(@const [$($elems)* (())] $($rest)*)
+    };
+    (@const_value [$($elems:tt)*] (true) $($rest:tt)*) => {
+        json_list!(@const [$($elems)* ($crate::base_types::JSONtrue)] $($rest)*)
+    };
+    (@const_value [$($elems:tt)*] (false) $($rest:tt)*) => {
+        json_list!(@const [$($elems)* ($crate::base_types::JSONfalse)] $($rest)*)
+    };
+    (@const_value [$($elems:tt)*] (json_object ! $body:tt) $($rest:tt)*) => {
+        json_object!(@if_empty $body
+            { json_list!(@const [$($elems)* ($crate::object::JSONObjectEnd{})] $($rest)*) }
+            { json_list!(@const_end [$($elems)*] json_object! $body, $($rest)*) })
+    };
+    (@const_value [$($elems:tt)*] (json_list ! $body:tt) $($rest:tt)*) => {
+        json_object!(@if_empty $body
+            { json_list!(@const [$($elems)* ($crate::list::JSONListEnd{})] $($rest)*) }
+            { json_list!(@const_end [$($elems)*] json_list! $body, $($rest)*) })
+    };
+    (@const_value [$($elems:tt)*] ($($elem:tt)*) $($rest:tt)*) => {
+        json_list!(@const_end [$($elems)*] $($elem)*, $($rest)*)
+    };
+    // An element that turned out not to be constant ends the run
+    (@const_end [] $elem:expr, $($rest:tt)*) => { json_list!($elem, $($rest)*) };
+    (@const_end [$($elems:tt)+] $elem:expr, $($rest:tt)*) => {
+        inlined_json_fragment!(list: [$($elems)+], next: json_list!($elem, $($rest)*))
+    };
 
     ($elem:expr , $($rest:tt)* ) => {
         $crate::list::JSONListElem::new(
@@ -207,6 +687,7 @@ macro_rules! json_list {
 mod tests {
     // Note this useful idiom: importing names from outer (for mod tests) scope.
     use super::*;
+    use crate::*;
 
     #[test]
     fn empty() {
@@ -231,4 +712,61 @@ mod tests {
         assert_eq!("[1,2]", json_list![1, 2,].to_json_string());
         assert_eq!("[1,2,3,4,5]", json_list![1, 2, 3, 4, 5].to_json_string());
     }
+
+    #[test]
+    fn len() {
+        fn check<T: JSONValue>(value: T) {
+            assert_eq!(Some(value.to_json_string().len()), value.json_len());
+        }
+        check(json_list![]);
+        check(json_list![1]);
+        check(json_list![1, "x", null, json_list![true, false]]);
+        check(Vec::<u8>::new());
+        check(vec![1]);
+        check(vec![vec![1, 2], vec![]]);
+        check(ToJSONList([1, 2, 3]));
+        assert_eq!(None, RefCell::new(vec![1].into_iter()).json_len());
+        assert_eq!(None, vec![RefCell::new(vec![1].into_iter())].json_len());
+    }
+
+    #[test]
+    fn pretty() {
+        assert_eq!("[]", json_list![].to_json_string_pretty());
+        assert_eq!("[\n  1,\n  2\n]", json_list![1, 2].to_json_string_pretty());
+        assert_eq!("[\n  []\n]", vec![Vec::<u8>::new()].to_json_string_pretty());
+        let iter = RefCell::new(vec![1, 2].into_iter());
+        assert_eq!("[\n  1,\n  2\n]", iter.to_json_string_pretty());
+    }
+
+    #[test]
+    fn builder() {
+        fn check<T: JSONValue>(expected: &str, value: T) {
+            assert_eq!(expected, value.to_json_string());
+            assert_eq!(Some(expected.len()), value.json_len());
+        }
+        check("[]", json_list![].append(json_list![]));
+        check("[1]", json_list![].push(1));
+        check("[1,2,3]", json_list![1].push(2).push(3));
+        check(
+            "[[1],1]",
+            json_list![json_list![].push(1).append(json_list![])].push(1),
+        );
+        check(
+            "[null,true,1,false,null]",
+            json_list![null, true]
+                .push(1)
+                .append(json_list![false, null]),
+        );
+        let nested = json_list![json_list![]].append(json_list![1].push(json_list![null]));
+        check("[[],1,[null]]", nested);
+        assert_eq!(
+            "[\n  [],\n  1,\n  [\n    null\n  ]\n]",
+            nested.to_json_string_pretty()
+        );
+        assert_eq!(
+            "[]",
+            json_list![].push(json_list![]).list.to_json_string_pretty()
+        );
+        assert_eq!(0, ::std::mem::size_of_val(&json_list![null].push(())));
+    }
 }
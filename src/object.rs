@@ -1,18 +1,37 @@
 //! Serialization to JSON objects like `{"x":1,"y":null}`
 
-use super::string::JSONString;
+use super::constant::{ConstFragment, ConstJSONValue};
+#[cfg(feature = "alloc")]
+use super::error::JSONError;
+use super::format::JSONFormatter;
+#[cfg(feature = "alloc")]
+use super::string::json_string_utf16;
+use super::string::{JSONString, StaticJSONKey};
+#[cfg(feature = "alloc")]
+use super::write::ByteBuffer;
+use super::write::JSONWrite;
 use super::JSONValue;
+#[cfg(feature = "alloc")]
+use alloc::collections::BTreeMap;
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+use core::fmt;
+use core::marker::PhantomData;
+#[cfg(feature = "indexmap")]
+use indexmap::IndexMap;
+#[cfg(feature = "std")]
 use std::collections::HashMap;
+#[cfg(feature = "std")]
 use std::hash::BuildHasher;
+#[cfg(feature = "std")]
 use std::hash::Hash;
-use std::io;
 
 /// Write a single key-value pair
-fn write_object_entry<W, K, V>(w: &mut W, key: &K, value: &V) -> io::Result<()>
+fn write_object_entry<W, K, V>(w: &mut W, key: &K, value: &V) -> Result<(), W::Error>
 where
-    W: io::Write,
+    W: JSONWrite,
     K: JSONString,
-    V: JSONValue,
+    V: JSONValue + ?Sized,
 {
     key.write_json(w)?;
     w.write_all(b":")?;
@@ -20,9 +39,9 @@ where
 }
 
 /// Write a list of key-value pairs to a writer as a json object
-fn write_object<'a, W, K, V, I>(w: &mut W, iter: &mut I) -> io::Result<()>
+pub(crate) fn write_object<'a, W, K, V, I>(w: &mut W, iter: &mut I) -> Result<(), W::Error>
 where
-    W: io::Write,
+    W: JSONWrite,
     K: JSONString,
     V: JSONValue,
     V: 'a,
@@ -40,6 +59,313 @@ where
     w.write_all(b"}")
 }
 
+/// Write a list of key-value pairs to a writer as a json object, using the given formatter
+pub(crate) fn write_object_formatted<'a, W, K, V, I>(
+    w: &mut W,
+    f: &mut JSONFormatter,
+    iter: &mut I,
+) -> Result<(), W::Error>
+where
+    W: JSONWrite,
+    K: JSONString,
+    V: JSONValue,
+    V: 'a,
+    K: 'a,
+    I: Iterator<Item = (&'a K, &'a V)>,
+{
+    #[cfg(feature = "alloc")]
+    {
+        if f.sorts_keys() {
+            return write_object_sorted(w, f, iter);
+        }
+    }
+    f.begin_object(w)?;
+    let mut empty = true;
+    for (key, value) in iter {
+        f.begin_object_key(w, empty)?;
+        key.write_json_formatted(w, f)?;
+        f.begin_object_value(w)?;
+        value.write_json_formatted(w, f)?;
+        empty = false;
+    }
+    f.end_object(w, empty)
+}
+
+/// Write a list of key-value pairs to a writer as a json object, sorted by key.
+/// Only the keys are serialized in advance.
+#[cfg(feature = "alloc")]
+fn write_object_sorted<'a, W, K, V, I>(
+    w: &mut W,
+    f: &mut JSONFormatter,
+    iter: &mut I,
+) -> Result<(), W::Error>
+where
+    W: JSONWrite,
+    K: JSONString,
+    V: JSONValue,
+    V: 'a,
+    K: 'a,
+    I: Iterator<Item = (&'a K, &'a V)>,
+{
+    let mut entries = iter
+        .map(|(key, value)| Ok((SortKey::new(key, f)?, value)))
+        .collect::<Result<Vec<_>, JSONError>>()?;
+    entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+    f.begin_object(w)?;
+    for (i, (key, value)) in entries.iter().enumerate() {
+        f.begin_object_key(w, i == 0)?;
+        w.write_all(&key.json)?;
+        f.begin_object_value(w)?;
+        value.write_json_formatted(w, f)?;
+    }
+    f.end_object(w, entries.is_empty())
+}
+
+/// An object key, serialized in advance to be sorted
+#[cfg(feature = "alloc")]
+#[derive(PartialEq, Eq)]
+struct SortKey {
+    json: Vec<u8>,
+    utf16: Vec<u16>,
+}
+
+#[cfg(feature = "alloc")]
+impl SortKey {
+    fn new<K: JSONString>(key: &K, f: &mut JSONFormatter) -> Result<SortKey, JSONError> {
+        let mut json = ByteBuffer(Vec::new());
+        key.write_json_formatted(&mut json, f)?;
+        let utf16 = json_string_utf16(&json.0);
+        Ok(SortKey {
+            json: json.0,
+            utf16,
+        })
+    }
+}
+
+/// Keys are sorted by the UTF-16 code units of the string they represent,
+/// as required by [RFC 8785](https://www.rfc-editor.org/rfc/rfc8785#section-3.2.3)
+#[cfg(feature = "alloc")]
+impl Ord for SortKey {
+    fn cmp(&self, other: &Self) -> core::cmp::Ordering {
+        self.utf16.cmp(&other.utf16)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl PartialOrd for SortKey {
+    fn partial_cmp(&self, other: &Self) -> Option<core::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Receives the entries of an object, in their natural order.
+/// See [`ObjectEntries`](trait.ObjectEntries.html).
+pub trait EntryVisitor {
+    /// The error that stops the visit
+    type Error;
+
+    /// Called for each entry of the object
+    fn visit_entry<K: JSONString, V: JSONValue + ?Sized>(
+        &mut self,
+        key: &K,
+        value: &V,
+    ) -> Result<(), Self::Error>;
+}
+
+/// Implemented by values that are serialized as objects, such as the ones created by
+/// [`json_object!`](../macro.json_object.html), derived structs, maps and
+/// [`ToJSONObject`](struct.ToJSONObject.html).
+/// It gives access to the entries without serializing them, to sort them,
+/// or to insert them in another object.
+pub trait ObjectEntries {
+    /// Calls the visitor once for each entry, in order
+    fn visit_entries<E: EntryVisitor>(&self, visitor: &mut E) -> Result<(), E::Error>;
+}
+
+impl<O: ObjectEntries + ?Sized> ObjectEntries for &O {
+    fn visit_entries<E: EntryVisitor>(&self, visitor: &mut E) -> Result<(), E::Error> {
+        (**self).visit_entries(visitor)
+    }
+}
+
+/// Visit a list of key-value pairs
+fn visit_entries<'a, E, K, V, I>(visitor: &mut E, iter: I) -> Result<(), E::Error>
+where
+    E: EntryVisitor,
+    K: JSONString,
+    V: JSONValue,
+    V: 'a,
+    K: 'a,
+    I: Iterator<Item = (&'a K, &'a V)>,
+{
+    for (key, value) in iter {
+        visitor.visit_entry(key, value)?;
+    }
+    Ok(())
+}
+
+/// Collects the entries of an object, serialized, to write them sorted by key
+#[cfg(feature = "alloc")]
+struct EntrySorter<'f> {
+    f: &'f mut JSONFormatter,
+    entries: Vec<(SortKey, Vec<u8>)>,
+}
+
+#[cfg(feature = "alloc")]
+impl EntryVisitor for EntrySorter<'_> {
+    type Error = JSONError;
+
+    fn visit_entry<K: JSONString, V: JSONValue + ?Sized>(
+        &mut self,
+        key: &K,
+        value: &V,
+    ) -> Result<(), JSONError> {
+        let key = SortKey::new(key, self.f)?;
+        let mut json = ByteBuffer(Vec::new());
+        value.write_json_formatted(&mut json, self.f)?;
+        self.entries.push((key, json.0));
+        Ok(())
+    }
+}
+
+/// Writes the entries of an object as they are visited, as members of another object
+struct MemberWriter<'a, W: JSONWrite> {
+    w: &'a mut W,
+    first: bool,
+}
+
+impl<W: JSONWrite> EntryVisitor for MemberWriter<'_, W> {
+    type Error = W::Error;
+
+    fn visit_entry<K: JSONString, V: JSONValue + ?Sized>(
+        &mut self,
+        key: &K,
+        value: &V,
+    ) -> Result<(), W::Error> {
+        self.w.write_all(if self.first { b"{" } else { b"," })?;
+        write_object_entry(self.w, key, value)?;
+        self.first = false;
+        Ok(())
+    }
+}
+
+/// Writes the entries of an object as they are visited, as members of another object,
+/// using the given formatter
+struct FormattedMemberWriter<'a, W: JSONWrite> {
+    w: &'a mut W,
+    f: &'a mut JSONFormatter,
+    first: bool,
+    /// Whether the object has to be opened before the first member
+    begin_object: bool,
+}
+
+impl<W: JSONWrite> EntryVisitor for FormattedMemberWriter<'_, W> {
+    type Error = W::Error;
+
+    fn visit_entry<K: JSONString, V: JSONValue + ?Sized>(
+        &mut self,
+        key: &K,
+        value: &V,
+    ) -> Result<(), W::Error> {
+        if self.begin_object {
+            self.f.begin_object(self.w)?;
+            self.begin_object = false;
+        }
+        self.f.begin_object_key(self.w, self.first)?;
+        key.write_json_formatted(self.w, self.f)?;
+        self.f.begin_object_value(self.w)?;
+        value.write_json_formatted(self.w, self.f)?;
+        self.first = false;
+        Ok(())
+    }
+}
+
+/// Computes the length of the members of an object, including the comma before each one
+#[derive(Default)]
+struct MembersLen {
+    len: usize,
+    count: usize,
+}
+
+impl EntryVisitor for MembersLen {
+    /// The length of an entry is unknown
+    type Error = ();
+
+    fn visit_entry<K: JSONString, V: JSONValue + ?Sized>(
+        &mut self,
+        key: &K,
+        value: &V,
+    ) -> Result<(), ()> {
+        self.len += 1 + key.json_len().ok_or(())? + 1 + value.json_len().ok_or(())?;
+        self.count += 1;
+        Ok(())
+    }
+}
+
+/// Writes an object with its entries sorted by key,
+/// when the formatter [sorts keys](../format/struct.JSONFormatter.html#method.sort_keys).
+///
+/// The values are serialized to memory before being written.
+/// Without the `alloc` feature, keys cannot be sorted, and entries are written in order.
+pub fn write_sorted_entries<O, W>(
+    object: &O,
+    w: &mut W,
+    f: &mut JSONFormatter,
+) -> Result<(), W::Error>
+where
+    O: ObjectEntries + ?Sized,
+    W: JSONWrite,
+{
+    f.begin_object(w)?;
+    #[cfg(feature = "alloc")]
+    {
+        let mut sorter = EntrySorter {
+            f: &mut *f,
+            entries: Vec::new(),
+        };
+        object.visit_entries(&mut sorter)?;
+        let mut entries = sorter.entries;
+        entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+        for (i, (key, value)) in entries.iter().enumerate() {
+            f.begin_object_key(w, i == 0)?;
+            w.write_all(&key.json)?;
+            f.begin_object_value(w)?;
+            w.write_all(value)?;
+        }
+        f.end_object(w, entries.is_empty())
+    }
+    #[cfg(not(feature = "alloc"))]
+    {
+        let mut writer = FormattedMemberWriter {
+            w: &mut *w,
+            f: &mut *f,
+            first: true,
+            begin_object: false,
+        };
+        object.visit_entries(&mut writer)?;
+        let empty = writer.first;
+        f.end_object(w, empty)
+    }
+}
+
+/// Length of a json object containing the given key-value pairs
+pub(crate) fn json_len_object<'a, K, V, I>(iter: I) -> Option<usize>
+where
+    K: JSONString,
+    V: JSONValue,
+    V: 'a,
+    K: 'a,
+    I: Iterator<Item = (&'a K, &'a V)>,
+{
+    let mut count = 0;
+    let mut len = 2; // { and }
+    for (key, value) in iter {
+        len += key.json_len()? + 1 + value.json_len()?;
+        count += 1;
+    }
+    Some(len + count.max(1) - 1) // commas
+}
+
 /// A struct used to wrap another type and make it serializable as a json object.
 /// The other type has to be able to yield (key, value) pairs by implementing IntoIterator.
 ///
@@ -67,123 +393,1214 @@ where
     V: JSONValue,
     for<'a> &'a I: IntoIterator<Item = &'a (K, V)>,
 {
-    fn write_json<W: io::Write>(&self, w: &mut W) -> io::Result<()> {
+    fn write_json<W: JSONWrite>(&self, w: &mut W) -> Result<(), W::Error> {
         let mut iter = (&self.0).into_iter().map(|(k, v)| (k, v)); // Convert a borrowed tuple to a tuple of borrowed values
         write_object(w, &mut iter)
     }
+
+    fn write_json_formatted<W: JSONWrite>(
+        &self,
+        w: &mut W,
+        f: &mut JSONFormatter,
+    ) -> Result<(), W::Error> {
+        let mut iter = (&self.0).into_iter().map(|(k, v)| (k, v));
+        write_object_formatted(w, f, &mut iter)
+    }
+
+    fn json_len(&self) -> Option<usize> {
+        json_len_object((&self.0).into_iter().map(|(k, v)| (k, v)))
+    }
+}
+
+impl<K, V, I> ObjectEntries for ToJSONObject<K, V, I>
+where
+    K: JSONString,
+    V: JSONValue,
+    for<'a> &'a I: IntoIterator<Item = &'a (K, V)>,
+{
+    fn visit_entries<E: EntryVisitor>(&self, visitor: &mut E) -> Result<(), E::Error> {
+        visit_entries(visitor, (&self.0).into_iter().map(|(k, v)| (k, v)))
+    }
+}
+
+/// Serialize a HashMap to a JSON object. The property order is not guaranteed.
+///
+/// To get the same output for equal maps, wrap the map in [`Sorted`](struct.Sorted.html),
+/// or use a formatter that [sorts keys](../format/struct.JSONFormatter.html#method.sort_keys).
+#[cfg(feature = "std")]
+impl<K: JSONString + Eq + Hash, V: JSONValue, S: BuildHasher> JSONValue for HashMap<K, V, S> {
+    fn write_json<W: JSONWrite>(&self, w: &mut W) -> Result<(), W::Error> {
+        write_object(w, &mut self.iter())
+    }
+
+    fn write_json_formatted<W: JSONWrite>(
+        &self,
+        w: &mut W,
+        f: &mut JSONFormatter,
+    ) -> Result<(), W::Error> {
+        write_object_formatted(w, f, &mut self.iter())
+    }
+
+    fn json_len(&self) -> Option<usize> {
+        json_len_object(self.iter())
+    }
+}
+
+#[cfg(feature = "std")]
+impl<K: JSONString + Eq + Hash, V: JSONValue, S: BuildHasher> ObjectEntries for HashMap<K, V, S> {
+    fn visit_entries<E: EntryVisitor>(&self, visitor: &mut E) -> Result<(), E::Error> {
+        visit_entries(visitor, self.iter())
+    }
+}
+
+/// Serializes a HashMap to a JSON object whose keys are sorted, whatever the formatter.
+///
+/// Keys are ordered by the UTF-16 code units of their JSON string form,
+/// like with [`JSONFormatter::sort_keys`](../format/struct.JSONFormatter.html#method.sort_keys).
+/// Only the keys are serialized in advance: the values are neither cloned nor buffered.
+///
+/// # Examples
+///
+/// ```
+/// use std::collections::HashMap;
+/// use json_in_type::JSONValue;
+/// use json_in_type::object::Sorted;
+///
+/// let map: HashMap<_, _> = vec![("b", 2), ("c", 3), ("a", 1)].into_iter().collect();
+///
+/// assert_eq!(r#"{"a":1,"b":2,"c":3}"#, Sorted(&map).to_json_string());
+/// ```
+#[cfg(feature = "std")]
+pub struct Sorted<M>(pub M);
+
+#[cfg(feature = "std")]
+impl<K: JSONString + Eq + Hash, V: JSONValue, S: BuildHasher> JSONValue
+    for Sorted<&HashMap<K, V, S>>
+{
+    fn write_json<W: JSONWrite>(&self, w: &mut W) -> Result<(), W::Error> {
+        write_object_sorted(w, &mut JSONFormatter::compact(), &mut self.0.iter())
+    }
+
+    fn write_json_formatted<W: JSONWrite>(
+        &self,
+        w: &mut W,
+        f: &mut JSONFormatter,
+    ) -> Result<(), W::Error> {
+        write_object_sorted(w, f, &mut self.0.iter())
+    }
+
+    fn json_len(&self) -> Option<usize> {
+        self.0.json_len()
+    }
+}
+
+#[cfg(feature = "std")]
+impl<K: JSONString + Eq + Hash, V: JSONValue, S: BuildHasher> JSONValue
+    for Sorted<HashMap<K, V, S>>
+{
+    fn write_json<W: JSONWrite>(&self, w: &mut W) -> Result<(), W::Error> {
+        Sorted(&self.0).write_json(w)
+    }
+
+    fn write_json_formatted<W: JSONWrite>(
+        &self,
+        w: &mut W,
+        f: &mut JSONFormatter,
+    ) -> Result<(), W::Error> {
+        Sorted(&self.0).write_json_formatted(w, f)
+    }
+
+    fn json_len(&self) -> Option<usize> {
+        self.0.json_len()
+    }
+}
+
+/// Serialize a BTreeMap to a JSON object, in the order of its keys
+#[cfg(feature = "alloc")]
+impl<K: JSONString + Ord, V: JSONValue> JSONValue for BTreeMap<K, V> {
+    fn write_json<W: JSONWrite>(&self, w: &mut W) -> Result<(), W::Error> {
+        write_object(w, &mut self.iter())
+    }
+
+    fn write_json_formatted<W: JSONWrite>(
+        &self,
+        w: &mut W,
+        f: &mut JSONFormatter,
+    ) -> Result<(), W::Error> {
+        write_object_formatted(w, f, &mut self.iter())
+    }
+
+    fn json_len(&self) -> Option<usize> {
+        json_len_object(self.iter())
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<K: JSONString + Ord, V: JSONValue> ObjectEntries for BTreeMap<K, V> {
+    fn visit_entries<E: EntryVisitor>(&self, visitor: &mut E) -> Result<(), E::Error> {
+        visit_entries(visitor, self.iter())
+    }
+}
+
+/// Serialize an IndexMap to a JSON object, in insertion order.
+/// This requires the `indexmap` feature.
+#[cfg(feature = "indexmap")]
+impl<K: JSONString, V: JSONValue, S> JSONValue for IndexMap<K, V, S> {
+    fn write_json<W: JSONWrite>(&self, w: &mut W) -> Result<(), W::Error> {
+        write_object(w, &mut self.iter())
+    }
+
+    fn write_json_formatted<W: JSONWrite>(
+        &self,
+        w: &mut W,
+        f: &mut JSONFormatter,
+    ) -> Result<(), W::Error> {
+        write_object_formatted(w, f, &mut self.iter())
+    }
+
+    fn json_len(&self) -> Option<usize> {
+        json_len_object(self.iter())
+    }
+}
+
+#[cfg(feature = "indexmap")]
+impl<K: JSONString, V: JSONValue, S> ObjectEntries for IndexMap<K, V, S> {
+    fn visit_entries<E: EntryVisitor>(&self, visitor: &mut E) -> Result<(), E::Error> {
+        visit_entries(visitor, self.iter())
+    }
+}
+
+pub trait JSONObject: JSONValue + ObjectEntries {
+    fn write_json_ending<W: JSONWrite>(&self, f: &mut W, first: bool) -> Result<(), W::Error>;
+    #[inline]
+    fn write_json_full<W: JSONWrite>(&self, w: &mut W) -> Result<(), W::Error> {
+        self.write_json_ending(w, true)
+    }
+    fn write_json_ending_formatted<W: JSONWrite>(
+        &self,
+        w: &mut W,
+        f: &mut JSONFormatter,
+        first: bool,
+    ) -> Result<(), W::Error>;
+    #[inline]
+    fn write_json_full_formatted<W: JSONWrite>(
+        &self,
+        w: &mut W,
+        f: &mut JSONFormatter,
+    ) -> Result<(), W::Error> {
+        self.write_json_ending_formatted(w, f, true)
+    }
+    fn json_len_ending(&self, first: bool) -> Option<usize>;
+
+    /// Returns an object with the members of this object, followed by the members of `other`,
+    /// that can be another static object, a derived struct, a map or a
+    /// [`ToJSONObject`](struct.ToJSONObject.html).
+    ///
+    /// Keys are not deduplicated: a key present in both objects is written twice.
+    /// Most JSON parsers keep the last value, so the members of `other` take precedence.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use json_in_type::*;
+    /// use json_in_type::object::{JSONObject, ToJSONObject};
+    ///
+    /// let base = json_object! { version: 2 };
+    /// let merged = base.merge(ToJSONObject(vec![("id", 7)]));
+    /// assert_eq!(r#"{"version":2,"id":7}"#, merged.to_json_string());
+    /// ```
+    fn merge<O: ObjectEntries>(
+        self,
+        other: O,
+    ) -> JSONObjectSpread<Self, JSONObjectSpread<O, JSONObjectEnd>>
+    where
+        Self: Sized,
+    {
+        JSONObjectSpread {
+            object: self,
+            next: JSONObjectSpread {
+                object: other,
+                next: JSONObjectEnd,
+            },
+        }
+    }
+
+    /// Returns an object with the members of this object, followed by `key: value`.
+    ///
+    /// Objects can be built step by step, across functions,
+    /// and the result is still a static structure whose type stores the shape of the object.
+    /// The key is not deduplicated.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use json_in_type::*;
+    /// use json_in_type::object::JSONObject;
+    ///
+    /// fn with_status<O: JSONObject>(response: O, status: u16) -> impl JSONObject {
+    ///     response.with_key("status", status)
+    /// }
+    ///
+    /// let response = with_status(json_object! { ok: false }, 404);
+    /// assert_eq!(r#"{"ok":false,"status":404}"#, response.to_json_string());
+    /// ```
+    fn with_key<K: JSONString, V: JSONValue>(
+        self,
+        key: K,
+        value: V,
+    ) -> JSONObjectSpread<Self, JSONObjectEntry<K, V, JSONObjectEnd>>
+    where
+        Self: Sized,
+    {
+        JSONObjectSpread {
+            object: self,
+            next: JSONObjectEntry {
+                key,
+                value,
+                next: JSONObjectEnd,
+            },
+        }
+    }
+
+    /// Like [`with_key`](#method.with_key), for a key known at compile time,
+    /// created by [`json_key!`](../macro.json_key.html).
+    /// The key is escaped at compile time, and written with its separator in a single call to
+    /// `write_all`, as in [`json_object!`](../macro.json_object.html).
+    ///
+    /// Strings cannot be used as generic parameters, so the key is passed as a zero-sized value
+    /// rather than as a type.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use json_in_type::*;
+    /// use json_in_type::object::JSONObject;
+    ///
+    /// let page = 3;
+    /// let obj = json_object! { items: json_list![1, 2] }
+    ///     .with_static(json_key!(page), page)
+    ///     .with_static(json_key!("next-page"), page + 1);
+    /// assert_eq!(r#"{"items":[1,2],"page":3,"next-page":4}"#, obj.to_json_string());
+    /// ```
+    fn with_static<K: StaticJSONKey, V: JSONValue>(
+        self,
+        key: K,
+        value: V,
+    ) -> JSONObjectSpread<Self, StaticJSONObjectEntry<K, V, JSONObjectEnd>>
+    where
+        Self: Sized,
+    {
+        JSONObjectSpread {
+            object: self,
+            next: StaticJSONObjectEntry {
+                key,
+                value,
+                next: JSONObjectEnd,
+            },
+        }
+    }
+}
+
+/// A JSON object stored as a static linked list.
+/// This is a generic structure that specializes at compile-time
+/// to a structure whose type stores the exact shape of the object.
+#[derive(Clone, Copy, Debug)]
+pub struct JSONObjectEntry<K: JSONString, V: JSONValue, U: JSONObject> {
+    pub key: K,
+    pub value: V,
+    pub next: U,
+}
+
+impl<K: JSONString, V: JSONValue, U: JSONObject> JSONObject for JSONObjectEntry<K, V, U> {
+    #[inline(always)]
+    fn write_json_ending<W: JSONWrite>(&self, w: &mut W, first: bool) -> Result<(), W::Error> {
+        w.write_all(if first { b"{" } else { b"," })?;
+        self.key.write_json(w)?;
+        w.write_all(b":")?;
+        self.value.write_json(w)?;
+        self.next.write_json_ending(w, false)
+    }
+
+    #[inline(always)]
+    fn write_json_ending_formatted<W: JSONWrite>(
+        &self,
+        w: &mut W,
+        f: &mut JSONFormatter,
+        first: bool,
+    ) -> Result<(), W::Error> {
+        if first {
+            if f.sorts_keys() {
+                return write_sorted_entries(self, w, f);
+            }
+            f.begin_object(w)?;
+        }
+        f.begin_object_key(w, first)?;
+        self.key.write_json_formatted(w, f)?;
+        f.begin_object_value(w)?;
+        self.value.write_json_formatted(w, f)?;
+        self.next.write_json_ending_formatted(w, f, false)
+    }
+
+    #[inline]
+    fn json_len_ending(&self, _first: bool) -> Option<usize> {
+        let entry_len = 1 + self.key.json_len()? + 1 + self.value.json_len()?;
+        Some(entry_len + self.next.json_len_ending(false)?)
+    }
+}
+
+impl<K: JSONString, V: JSONValue, U: JSONObject> ObjectEntries for JSONObjectEntry<K, V, U> {
+    fn visit_entries<E: EntryVisitor>(&self, visitor: &mut E) -> Result<(), E::Error> {
+        visitor.visit_entry(&self.key, &self.value)?;
+        self.next.visit_entries(visitor)
+    }
+}
+
+impl<K: JSONString, V: JSONValue, U: JSONObject> JSONValue for JSONObjectEntry<K, V, U> {
+    #[inline(always)]
+    fn write_json<W: JSONWrite>(&self, w: &mut W) -> Result<(), W::Error> {
+        self.write_json_full(w)
+    }
+
+    fn write_json_formatted<W: JSONWrite>(
+        &self,
+        w: &mut W,
+        f: &mut JSONFormatter,
+    ) -> Result<(), W::Error> {
+        self.write_json_full_formatted(w, f)
+    }
+
+    fn json_len(&self) -> Option<usize> {
+        self.json_len_ending(true)
+    }
+}
+
+/// An entry of a JSON object whose key is known at compile time.
+/// The key is written together with the preceding brace or comma and the following colon.
+/// It is created by [`json_object!`](../macro.json_object.html) for literal keys,
+/// and by [`JSONObject::with_static`](trait.JSONObject.html#method.with_static).
+#[derive(Clone, Copy, Debug)]
+pub struct StaticJSONObjectEntry<K: StaticJSONKey, V: JSONValue, U: JSONObject> {
+    pub key: K,
+    pub value: V,
+    pub next: U,
+}
+
+impl<K: StaticJSONKey, V: JSONValue, U: JSONObject> JSONObject for StaticJSONObjectEntry<K, V, U> {
+    #[inline(always)]
+    fn write_json_ending<W: JSONWrite>(&self, w: &mut W, first: bool) -> Result<(), W::Error> {
+        w.write_all(if first {
+            K::FIRST_PREFIX
+        } else {
+            K::NEXT_PREFIX
+        })?;
+        self.value.write_json(w)?;
+        self.next.write_json_ending(w, false)
+    }
+
+    #[inline(always)]
+    fn write_json_ending_formatted<W: JSONWrite>(
+        &self,
+        w: &mut W,
+        f: &mut JSONFormatter,
+        first: bool,
+    ) -> Result<(), W::Error> {
+        if first {
+            if f.sorts_keys() {
+                return write_sorted_entries(self, w, f);
+            }
+            f.begin_object(w)?;
+        }
+        f.begin_object_key(w, first)?;
+        f.write_string(w, K::KEY)?;
+        f.begin_object_value(w)?;
+        self.value.write_json_formatted(w, f)?;
+        self.next.write_json_ending_formatted(w, f, false)
+    }
+
+    #[inline]
+    fn json_len_ending(&self, _first: bool) -> Option<usize> {
+        let key_len = K::FIRST_PREFIX.len();
+        Some(key_len + self.value.json_len()? + self.next.json_len_ending(false)?)
+    }
+}
+
+impl<K: StaticJSONKey, V: JSONValue, U: JSONObject> ObjectEntries
+    for StaticJSONObjectEntry<K, V, U>
+{
+    fn visit_entries<E: EntryVisitor>(&self, visitor: &mut E) -> Result<(), E::Error> {
+        visitor.visit_entry(&self.key, &self.value)?;
+        self.next.visit_entries(visitor)
+    }
+}
+
+impl<K: StaticJSONKey, V: JSONValue, U: JSONObject> JSONValue for StaticJSONObjectEntry<K, V, U> {
+    #[inline(always)]
+    fn write_json<W: JSONWrite>(&self, w: &mut W) -> Result<(), W::Error> {
+        self.write_json_full(w)
+    }
+
+    fn write_json_formatted<W: JSONWrite>(
+        &self,
+        w: &mut W,
+        f: &mut JSONFormatter,
+    ) -> Result<(), W::Error> {
+        self.write_json_full_formatted(w, f)
+    }
+
+    fn json_len(&self) -> Option<usize> {
+        self.json_len_ending(true)
+    }
+}
+
+/// An entry of a JSON object that is present only when its value is `Some`.
+/// It is created by [`json_object!`](../macro.json_object.html) for optional and conditional
+/// keys that are computed at runtime.
+#[derive(Clone, Copy, Debug)]
+pub struct OptionalJSONObjectEntry<K: JSONString, V: JSONValue, U: JSONObject> {
+    pub key: K,
+    pub value: Option<V>,
+    pub next: U,
+}
+
+impl<K: JSONString, V: JSONValue, U: JSONObject> JSONObject for OptionalJSONObjectEntry<K, V, U> {
+    #[inline(always)]
+    fn write_json_ending<W: JSONWrite>(&self, w: &mut W, first: bool) -> Result<(), W::Error> {
+        if let Some(value) = &self.value {
+            w.write_all(if first { b"{" } else { b"," })?;
+            self.key.write_json(w)?;
+            w.write_all(b":")?;
+            value.write_json(w)?;
+            self.next.write_json_ending(w, false)
+        } else {
+            self.next.write_json_ending(w, first)
+        }
+    }
+
+    #[inline(always)]
+    fn write_json_ending_formatted<W: JSONWrite>(
+        &self,
+        w: &mut W,
+        f: &mut JSONFormatter,
+        first: bool,
+    ) -> Result<(), W::Error> {
+        let value = match &self.value {
+            Some(value) => value,
+            None => return self.next.write_json_ending_formatted(w, f, first),
+        };
+        if first {
+            if f.sorts_keys() {
+                return write_sorted_entries(self, w, f);
+            }
+            f.begin_object(w)?;
+        }
+        f.begin_object_key(w, first)?;
+        self.key.write_json_formatted(w, f)?;
+        f.begin_object_value(w)?;
+        value.write_json_formatted(w, f)?;
+        self.next.write_json_ending_formatted(w, f, false)
+    }
+
+    #[inline]
+    fn json_len_ending(&self, first: bool) -> Option<usize> {
+        match &self.value {
+            Some(value) => {
+                let entry_len = 1 + self.key.json_len()? + 1 + value.json_len()?;
+                Some(entry_len + self.next.json_len_ending(false)?)
+            }
+            None => self.next.json_len_ending(first),
+        }
+    }
+}
+
+impl<K: JSONString, V: JSONValue, U: JSONObject> ObjectEntries
+    for OptionalJSONObjectEntry<K, V, U>
+{
+    fn visit_entries<E: EntryVisitor>(&self, visitor: &mut E) -> Result<(), E::Error> {
+        if let Some(value) = &self.value {
+            visitor.visit_entry(&self.key, value)?;
+        }
+        self.next.visit_entries(visitor)
+    }
+}
+
+impl<K: JSONString, V: JSONValue, U: JSONObject> JSONValue for OptionalJSONObjectEntry<K, V, U> {
+    #[inline(always)]
+    fn write_json<W: JSONWrite>(&self, w: &mut W) -> Result<(), W::Error> {
+        self.write_json_full(w)
+    }
+
+    fn write_json_formatted<W: JSONWrite>(
+        &self,
+        w: &mut W,
+        f: &mut JSONFormatter,
+    ) -> Result<(), W::Error> {
+        self.write_json_full_formatted(w, f)
+    }
+
+    fn json_len(&self) -> Option<usize> {
+        self.json_len_ending(true)
+    }
+}
+
+/// An entry of a JSON object whose key is known at compile time,
+/// that is present only when its value is `Some`.
+/// Like in a [`StaticJSONObjectEntry`](struct.StaticJSONObjectEntry.html),
+/// the key is written together with the preceding brace or comma and the following colon.
+/// It is created by [`json_object!`](../macro.json_object.html) for optional and conditional
+/// keys that are identifiers or literals.
+#[derive(Clone, Copy, Debug)]
+pub struct StaticOptionalJSONObjectEntry<K: StaticJSONKey, V: JSONValue, U: JSONObject> {
+    pub key: K,
+    pub value: Option<V>,
+    pub next: U,
+}
+
+impl<K: StaticJSONKey, V: JSONValue, U: JSONObject> JSONObject
+    for StaticOptionalJSONObjectEntry<K, V, U>
+{
+    #[inline(always)]
+    fn write_json_ending<W: JSONWrite>(&self, w: &mut W, first: bool) -> Result<(), W::Error> {
+        if let Some(value) = &self.value {
+            w.write_all(if first {
+                K::FIRST_PREFIX
+            } else {
+                K::NEXT_PREFIX
+            })?;
+            value.write_json(w)?;
+            self.next.write_json_ending(w, false)
+        } else {
+            self.next.write_json_ending(w, first)
+        }
+    }
+
+    #[inline(always)]
+    fn write_json_ending_formatted<W: JSONWrite>(
+        &self,
+        w: &mut W,
+        f: &mut JSONFormatter,
+        first: bool,
+    ) -> Result<(), W::Error> {
+        let value = match &self.value {
+            Some(value) => value,
+            None => return self.next.write_json_ending_formatted(w, f, first),
+        };
+        if first {
+            if f.sorts_keys() {
+                return write_sorted_entries(self, w, f);
+            }
+            f.begin_object(w)?;
+        }
+        f.begin_object_key(w, first)?;
+        f.write_string(w, K::KEY)?;
+        f.begin_object_value(w)?;
+        value.write_json_formatted(w, f)?;
+        self.next.write_json_ending_formatted(w, f, false)
+    }
+
+    #[inline]
+    fn json_len_ending(&self, first: bool) -> Option<usize> {
+        match &self.value {
+            Some(value) => {
+                let key_len = K::FIRST_PREFIX.len();
+                Some(key_len + value.json_len()? + self.next.json_len_ending(false)?)
+            }
+            None => self.next.json_len_ending(first),
+        }
+    }
+}
+
+impl<K: StaticJSONKey, V: JSONValue, U: JSONObject> ObjectEntries
+    for StaticOptionalJSONObjectEntry<K, V, U>
+{
+    fn visit_entries<E: EntryVisitor>(&self, visitor: &mut E) -> Result<(), E::Error> {
+        if let Some(value) = &self.value {
+            visitor.visit_entry(&self.key, value)?;
+        }
+        self.next.visit_entries(visitor)
+    }
+}
+
+impl<K: StaticJSONKey, V: JSONValue, U: JSONObject> JSONValue
+    for StaticOptionalJSONObjectEntry<K, V, U>
+{
+    #[inline(always)]
+    fn write_json<W: JSONWrite>(&self, w: &mut W) -> Result<(), W::Error> {
+        self.write_json_full(w)
+    }
+
+    fn write_json_formatted<W: JSONWrite>(
+        &self,
+        w: &mut W,
+        f: &mut JSONFormatter,
+    ) -> Result<(), W::Error> {
+        self.write_json_full_formatted(w, f)
+    }
+
+    fn json_len(&self) -> Option<usize> {
+        self.json_len_ending(true)
+    }
+}
+
+/// The members of an object, inserted in another JSON object.
+/// It is created by the `..other` syntax of [`json_object!`](../macro.json_object.html),
+/// and by [`JSONObject::merge`](trait.JSONObject.html#method.merge).
+#[derive(Clone, Copy, Debug)]
+pub struct JSONObjectSpread<O: ObjectEntries, U: JSONObject> {
+    pub object: O,
+    pub next: U,
+}
+
+impl<O: ObjectEntries, U: JSONObject> JSONObject for JSONObjectSpread<O, U> {
+    #[inline(always)]
+    fn write_json_ending<W: JSONWrite>(&self, w: &mut W, first: bool) -> Result<(), W::Error> {
+        let mut writer = MemberWriter { w, first };
+        self.object.visit_entries(&mut writer)?;
+        let first = writer.first;
+        self.next.write_json_ending(w, first)
+    }
+
+    #[inline(always)]
+    fn write_json_ending_formatted<W: JSONWrite>(
+        &self,
+        w: &mut W,
+        f: &mut JSONFormatter,
+        first: bool,
+    ) -> Result<(), W::Error> {
+        if first && f.sorts_keys() {
+            return write_sorted_entries(self, w, f);
+        }
+        let mut writer = FormattedMemberWriter {
+            w,
+            f,
+            first,
+            begin_object: first,
+        };
+        self.object.visit_entries(&mut writer)?;
+        let first = writer.first;
+        self.next.write_json_ending_formatted(w, f, first)
+    }
+
+    #[inline]
+    fn json_len_ending(&self, first: bool) -> Option<usize> {
+        let mut members = MembersLen::default();
+        self.object.visit_entries(&mut members).ok()?;
+        if members.count == 0 {
+            self.next.json_len_ending(first)
+        } else {
+            Some(members.len + self.next.json_len_ending(false)?)
+        }
+    }
+}
+
+impl<O: ObjectEntries, U: JSONObject> ObjectEntries for JSONObjectSpread<O, U> {
+    fn visit_entries<E: EntryVisitor>(&self, visitor: &mut E) -> Result<(), E::Error> {
+        self.object.visit_entries(visitor)?;
+        self.next.visit_entries(visitor)
+    }
+}
+
+impl<O: ObjectEntries, U: JSONObject> JSONValue for JSONObjectSpread<O, U> {
+    #[inline(always)]
+    fn write_json<W: JSONWrite>(&self, w: &mut W) -> Result<(), W::Error> {
+        self.write_json_full(w)
+    }
+
+    fn write_json_formatted<W: JSONWrite>(
+        &self,
+        w: &mut W,
+        f: &mut JSONFormatter,
+    ) -> Result<(), W::Error> {
+        self.write_json_full_formatted(w, f)
+    }
+
+    fn json_len(&self) -> Option<usize> {
+        self.json_len_ending(true)
+    }
+}
+
+/// Constant members of an object, serialized at compile time, followed by other members.
+/// It is created by [`json_object!`](../macro.json_object.html) and [`json!`](../macro.json.html)
+/// for consecutive members whose keys and values are known at compile time:
+/// they are written with a single call to `write_all`.
+/// The entries are only used for formatted output.
+#[doc(hidden)]
+#[derive(Clone, Copy, Debug)]
+pub struct JSONObjectFragment<F: ConstFragment, O: ObjectEntries, U: JSONObject> {
+    pub fragment: F,
+    pub entries: O,
+    pub next: U,
+}
+
+impl<F: ConstFragment, O: ObjectEntries, U: JSONObject> JSONObject for JSONObjectFragment<F, O, U> {
+    #[inline(always)]
+    fn write_json_ending<W: JSONWrite>(&self, w: &mut W, first: bool) -> Result<(), W::Error> {
+        w.write_all(if first { F::FIRST } else { F::NEXT }.as_bytes())?;
+        if F::END {
+            Ok(())
+        } else {
+            self.next.write_json_ending(w, false)
+        }
+    }
+
+    #[inline(always)]
+    fn write_json_ending_formatted<W: JSONWrite>(
+        &self,
+        w: &mut W,
+        f: &mut JSONFormatter,
+        first: bool,
+    ) -> Result<(), W::Error> {
+        if first && f.sorts_keys() {
+            return write_sorted_entries(self, w, f);
+        }
+        let mut writer = FormattedMemberWriter {
+            w,
+            f,
+            first,
+            begin_object: first,
+        };
+        self.entries.visit_entries(&mut writer)?;
+        let first = writer.first;
+        self.next.write_json_ending_formatted(w, f, first)
+    }
+
+    #[inline]
+    fn json_len_ending(&self, _first: bool) -> Option<usize> {
+        // The fragment has the same length with { or ,
+        let len = F::FIRST.len();
+        if F::END {
+            Some(len)
+        } else {
+            Some(len + self.next.json_len_ending(false)?)
+        }
+    }
+}
+
+impl<F: ConstFragment, O: ObjectEntries, U: JSONObject> ObjectEntries
+    for JSONObjectFragment<F, O, U>
+{
+    fn visit_entries<E: EntryVisitor>(&self, visitor: &mut E) -> Result<(), E::Error> {
+        self.entries.visit_entries(visitor)?;
+        self.next.visit_entries(visitor)
+    }
+}
+
+impl<F: ConstFragment, O: ObjectEntries, U: JSONObject> JSONValue for JSONObjectFragment<F, O, U> {
+    #[inline(always)]
+    fn write_json<W: JSONWrite>(&self, w: &mut W) -> Result<(), W::Error> {
+        self.write_json_full(w)
+    }
+
+    fn write_json_formatted<W: JSONWrite>(
+        &self,
+        w: &mut W,
+        f: &mut JSONFormatter,
+    ) -> Result<(), W::Error> {
+        self.write_json_full_formatted(w, f)
+    }
+
+    fn json_len(&self) -> Option<usize> {
+        self.json_len_ending(true)
+    }
+}
+
+/// An object made only of constant members ends with a fragment that includes the closing brace
+impl<F: ConstFragment, O: ObjectEntries> ConstJSONValue
+    for JSONObjectFragment<F, O, JSONObjectEnd>
+{
+    const JSON: &'static str = F::FIRST;
+}
+
+/// An empty JSON object. This is a Zero Sized Type.
+/// It just serves to mark the end of an object in its type,
+/// but takes no space in memory at runtime.
+#[derive(Clone, Copy, Debug)]
+pub struct JSONObjectEnd;
+
+impl JSONObject for JSONObjectEnd {
+    #[inline(always)]
+    fn write_json_ending<W: JSONWrite>(&self, w: &mut W, first: bool) -> Result<(), W::Error> {
+        w.write_all(if first { b"{}" } else { b"}" })
+    }
+
+    #[inline(always)]
+    fn write_json_ending_formatted<W: JSONWrite>(
+        &self,
+        w: &mut W,
+        f: &mut JSONFormatter,
+        first: bool,
+    ) -> Result<(), W::Error> {
+        if first {
+            f.begin_object(w)?;
+        }
+        f.end_object(w, first)
+    }
+
+    #[inline]
+    fn json_len_ending(&self, first: bool) -> Option<usize> {
+        Some(if first { 2 } else { 1 })
+    }
+}
+
+impl ObjectEntries for JSONObjectEnd {
+    fn visit_entries<E: EntryVisitor>(&self, _visitor: &mut E) -> Result<(), E::Error> {
+        Ok(())
+    }
+}
+
+impl JSONValue for JSONObjectEnd {
+    fn write_json<W: JSONWrite>(&self, w: &mut W) -> Result<(), W::Error> {
+        self.write_json_full(w)
+    }
+
+    fn write_json_formatted<W: JSONWrite>(
+        &self,
+        w: &mut W,
+        f: &mut JSONFormatter,
+    ) -> Result<(), W::Error> {
+        self.write_json_full_formatted(w, f)
+    }
+
+    fn json_len(&self) -> Option<usize> {
+        self.json_len_ending(true)
+    }
+}
+
+/// Implemented by the static keys whose [`key_hash`](../string/fn.key_hash.html) is `HASH`
+#[doc(hidden)]
+pub trait KeyHash<const HASH: u128> {}
+
+/// The position of a member in a static object: the current entry
+#[doc(hidden)]
+pub struct Here;
+
+/// The position of a member in a static object: after the current entry, at position `I`
+#[doc(hidden)]
+pub struct There<I>(PhantomData<I>);
+
+/// Gives mutable access to the value of the member of a static object whose key hash is `HASH`.
+/// The position `I` of the member is inferred, and need not be specified.
+/// See [`json_field_mut!`](../macro.json_field_mut.html).
+#[doc(hidden)]
+pub trait FieldMut<const HASH: u128, I> {
+    type Value;
+
+    fn field_mut(&mut self) -> &mut Self::Value;
+}
+
+impl<K, V, U, const HASH: u128> FieldMut<HASH, Here> for StaticJSONObjectEntry<K, V, U>
+where
+    K: StaticJSONKey + KeyHash<HASH>,
+    V: JSONValue,
+    U: JSONObject,
+{
+    type Value = V;
+
+    fn field_mut(&mut self) -> &mut V {
+        &mut self.value
+    }
+}
+
+impl<K, V, U, I, const HASH: u128> FieldMut<HASH, There<I>> for StaticJSONObjectEntry<K, V, U>
+where
+    K: StaticJSONKey,
+    V: JSONValue,
+    U: JSONObject + FieldMut<HASH, I>,
+{
+    type Value = U::Value;
+
+    fn field_mut(&mut self) -> &mut U::Value {
+        self.next.field_mut()
+    }
+}
+
+impl<K, V, U, const HASH: u128> FieldMut<HASH, Here> for JSONObjectEntry<K, V, U>
+where
+    K: JSONString + KeyHash<HASH>,
+    V: JSONValue,
+    U: JSONObject,
+{
+    type Value = V;
+
+    fn field_mut(&mut self) -> &mut V {
+        &mut self.value
+    }
+}
+
+impl<K, V, U, I, const HASH: u128> FieldMut<HASH, There<I>> for JSONObjectEntry<K, V, U>
+where
+    K: JSONString,
+    V: JSONValue,
+    U: JSONObject + FieldMut<HASH, I>,
+{
+    type Value = U::Value;
+
+    fn field_mut(&mut self) -> &mut U::Value {
+        self.next.field_mut()
+    }
+}
+
+impl<K, V, U, const HASH: u128> FieldMut<HASH, Here> for OptionalJSONObjectEntry<K, V, U>
+where
+    K: JSONString + KeyHash<HASH>,
+    V: JSONValue,
+    U: JSONObject,
+{
+    type Value = Option<V>;
+
+    fn field_mut(&mut self) -> &mut Option<V> {
+        &mut self.value
+    }
+}
+
+impl<K, V, U, I, const HASH: u128> FieldMut<HASH, There<I>> for OptionalJSONObjectEntry<K, V, U>
+where
+    K: JSONString,
+    V: JSONValue,
+    U: JSONObject + FieldMut<HASH, I>,
+{
+    type Value = U::Value;
+
+    fn field_mut(&mut self) -> &mut U::Value {
+        self.next.field_mut()
+    }
+}
+
+impl<K, V, U, const HASH: u128> FieldMut<HASH, Here> for StaticOptionalJSONObjectEntry<K, V, U>
+where
+    K: StaticJSONKey + KeyHash<HASH>,
+    V: JSONValue,
+    U: JSONObject,
+{
+    type Value = Option<V>;
+
+    fn field_mut(&mut self) -> &mut Option<V> {
+        &mut self.value
+    }
+}
+
+impl<K, V, U, I, const HASH: u128> FieldMut<HASH, There<I>>
+    for StaticOptionalJSONObjectEntry<K, V, U>
+where
+    K: StaticJSONKey,
+    V: JSONValue,
+    U: JSONObject + FieldMut<HASH, I>,
+{
+    type Value = U::Value;
+
+    fn field_mut(&mut self) -> &mut U::Value {
+        self.next.field_mut()
+    }
+}
+
+/// The members of a spread object are not searched
+impl<O, U, I, const HASH: u128> FieldMut<HASH, There<I>> for JSONObjectSpread<O, U>
+where
+    O: ObjectEntries,
+    U: JSONObject + FieldMut<HASH, I>,
+{
+    type Value = U::Value;
+
+    fn field_mut(&mut self) -> &mut U::Value {
+        self.next.field_mut()
+    }
+}
+
+/// Constant members are serialized at compile time, and cannot be modified
+impl<F, O, U, I, const HASH: u128> FieldMut<HASH, There<I>> for JSONObjectFragment<F, O, U>
+where
+    F: ConstFragment,
+    O: ObjectEntries,
+    U: JSONObject + FieldMut<HASH, I>,
+{
+    type Value = U::Value;
+
+    fn field_mut(&mut self) -> &mut U::Value {
+        self.next.field_mut()
+    }
 }
 
-/// Serialize a HashMap to a JSON object. The property order is not guaranteed.
-impl<K: JSONString + Eq + Hash, V: JSONValue, S: BuildHasher> JSONValue for HashMap<K, V, S> {
-    fn write_json<W: io::Write>(&self, w: &mut W) -> io::Result<()> {
-        write_object(w, &mut self.iter())
+/// The `N`-th key of an object type declared with [`json_type!`](../macro.json_type.html).
+/// The declared type implements this trait once for each of its keys.
+#[doc(hidden)]
+pub trait JSONTypeKey<const N: usize> {
+    const KEY: &'static str;
+    const FIRST_PREFIX: &'static [u8];
+    const NEXT_PREFIX: &'static [u8];
+}
+
+/// A zero-sized key of an object type declared with [`json_type!`](../macro.json_type.html).
+/// Unlike the keys of [`json_object!`](../macro.json_object.html), it can be named.
+#[doc(hidden)]
+pub struct NamedJSONKey<T, const N: usize>(PhantomData<fn() -> T>);
+
+impl<T, const N: usize> NamedJSONKey<T, N> {
+    pub const fn new() -> NamedJSONKey<T, N> {
+        NamedJSONKey(PhantomData)
     }
 }
 
-pub trait JSONObject: JSONValue {
-    fn write_json_ending<W: io::Write>(&self, f: &mut W, first: bool) -> io::Result<()>;
-    #[inline]
-    fn write_json_full<W: io::Write>(&self, w: &mut W) -> io::Result<()> {
-        self.write_json_ending(w, true)
+impl<T, const N: usize> Default for NamedJSONKey<T, N> {
+    fn default() -> Self {
+        NamedJSONKey::new()
     }
 }
 
-/// A JSON object stored as a static linked list.
-/// This is a generic structure that specializes at compile-time
-/// to a structure whose type stores the exact shape of the object.
-pub struct JSONObjectEntry<K: JSONString, V: JSONValue, U: JSONObject> {
-    pub key: K,
-    pub value: V,
-    pub next: U,
+impl<T, const N: usize> Clone for NamedJSONKey<T, N> {
+    fn clone(&self) -> Self {
+        *self
+    }
 }
 
-impl<K: JSONString, V: JSONValue, U: JSONObject> JSONObject for JSONObjectEntry<K, V, U> {
-    #[inline(always)]
-    fn write_json_ending<W: io::Write>(&self, w: &mut W, first: bool) -> io::Result<()> {
-        w.write_all(if first { b"{" } else { b"," })?;
-        self.key.write_json(w)?;
-        w.write_all(b":")?;
-        self.value.write_json(w)?;
-        self.next.write_json_ending(w, false)
+impl<T, const N: usize> Copy for NamedJSONKey<T, N> {}
+
+impl<T: JSONTypeKey<N>, const N: usize> fmt::Debug for NamedJSONKey<T, N> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(T::KEY, f)
     }
 }
 
-impl<K: JSONString, V: JSONValue, U: JSONObject> JSONValue for JSONObjectEntry<K, V, U> {
+impl<T: JSONTypeKey<N>, const N: usize> JSONValue for NamedJSONKey<T, N> {
     #[inline(always)]
-    fn write_json<W: io::Write>(&self, w: &mut W) -> io::Result<()> {
-        self.write_json_full(w)
+    fn write_json<W: JSONWrite>(&self, w: &mut W) -> Result<(), W::Error> {
+        // The quoted key, without the brace and the colon around it
+        let prefix = T::FIRST_PREFIX;
+        w.write_all(&prefix[1..prefix.len() - 1])
     }
-}
 
-/// An empty JSON object. This is a Zero Sized Type.
-/// It just serves to mark the end of an object in its type,
-/// but takes no space in memory at runtime.
-pub struct JSONObjectEnd;
+    fn write_json_formatted<W: JSONWrite>(
+        &self,
+        w: &mut W,
+        f: &mut JSONFormatter,
+    ) -> Result<(), W::Error> {
+        f.write_string(w, T::KEY)
+    }
 
-impl JSONObject for JSONObjectEnd {
-    #[inline(always)]
-    fn write_json_ending<W: io::Write>(&self, w: &mut W, first: bool) -> io::Result<()> {
-        w.write_all(if first { b"{}" } else { b"}" })
+    fn json_len(&self) -> Option<usize> {
+        Some(T::FIRST_PREFIX.len() - 2)
     }
 }
 
-impl JSONValue for JSONObjectEnd {
-    fn write_json<W: io::Write>(&self, w: &mut W) -> io::Result<()> {
-        self.write_json_full(w)
-    }
+impl<T: JSONTypeKey<N>, const N: usize> JSONString for NamedJSONKey<T, N> {}
+
+impl<T: JSONTypeKey<N>, const N: usize> StaticJSONKey for NamedJSONKey<T, N> {
+    const KEY: &'static str = T::KEY;
+    const FIRST_PREFIX: &'static [u8] = T::FIRST_PREFIX;
+    const NEXT_PREFIX: &'static [u8] = T::NEXT_PREFIX;
 }
 
 #[macro_export]
 #[doc(hidden)]
 macro_rules! inlined_json_object {
-    (key : $key:ident, value : $value:expr, next : $next:expr) => {{
-        use $crate::object::JSONObject;
-        use $crate::JSONValue;
+    (key : $key:ident, value : $value:expr, next : $next:expr) => {
+        inlined_json_object! {
+            key_str: $crate::string::unraw_identifier(stringify!($key)),
+            value: $value,
+            next: $next
+        }
+    };
+    (key_str : $key:expr, value : $value:expr, next : $next:expr) => {
+        $crate::object::StaticJSONObjectEntry {
+            key: inlined_json_key!(key_str: $key),
+            value: $value,
+            next: $next,
+        }
+    };
+}
+
+/// A zero-sized JSON string whose contents are known at compile time
+#[macro_export]
+#[doc(hidden)]
+macro_rules! inlined_json_key {
+    ($key:ident) => {
+        inlined_json_key!(key_str: $crate::string::unraw_identifier(stringify!($key)))
+    };
+    (key_str : $key:expr) => {{
+        #[derive(Clone, Copy)]
+        struct InlinedJSONKey;
 
-        struct InlinedJSONObjectEntry<V: JSONValue, U: JSONObject> {
-            value: V,
-            next: U,
+        // The key, escaped and quoted at compile time, alone and as the prefix of a member
+        impl InlinedJSONKey {
+            const KEY: &'static str = $key;
+            const QUOTED_LEN: usize = $crate::string::quoted_key_len(InlinedJSONKey::KEY);
+            const QUOTED: [u8; InlinedJSONKey::QUOTED_LEN] =
+                $crate::string::quoted_key(InlinedJSONKey::KEY);
+            const PREFIX_LEN: usize = $crate::string::member_prefix_len(InlinedJSONKey::KEY);
+            const FIRST_BYTES: [u8; InlinedJSONKey::PREFIX_LEN] =
+                $crate::string::member_prefix(InlinedJSONKey::KEY, b'{');
+            const NEXT_BYTES: [u8; InlinedJSONKey::PREFIX_LEN] =
+                $crate::string::member_prefix(InlinedJSONKey::KEY, b',');
         }
 
-        impl<V: JSONValue, U: JSONObject> JSONObject for InlinedJSONObjectEntry<V, U> {
+        impl $crate::JSONValue for InlinedJSONKey {
             #[inline(always)]
-            fn write_json_ending<W: ::std::io::Write>(
+            fn write_json<W: $crate::write::JSONWrite>(
                 &self,
                 w: &mut W,
-                first: bool,
-            ) -> ::std::io::Result<()> {
-                w.write_all(
-                    if first {
-                        concat!("{\"", stringify!($key), "\":")
-                    } else {
-                        concat!(",\"", stringify!($key), "\":")
-                    }
-                    .as_bytes(),
-                )?;
-                self.value.write_json(w)?;
-                self.next.write_json_ending(w, false)
+            ) -> ::core::result::Result<(), W::Error> {
+                w.write_all(&InlinedJSONKey::QUOTED)
             }
+
+            fn write_json_formatted<W: $crate::write::JSONWrite>(
+                &self,
+                w: &mut W,
+                f: &mut $crate::format::JSONFormatter,
+            ) -> ::core::result::Result<(), W::Error> {
+                f.write_string(w, InlinedJSONKey::KEY)
+            }
+
+            fn json_len(&self) -> Option<usize> {
+                Some(InlinedJSONKey::QUOTED_LEN)
+            }
+        }
+
+        impl $crate::string::JSONString for InlinedJSONKey {}
+
+        impl $crate::object::KeyHash<{ $crate::string::key_hash(InlinedJSONKey::KEY) }>
+            for InlinedJSONKey
+        {
         }
 
-        impl<V: JSONValue, U: JSONObject> JSONValue for InlinedJSONObjectEntry<V, U> {
-            fn write_json<W: ::std::io::Write>(&self, w: &mut W) -> ::std::io::Result<()> {
-                self.write_json_full(w)
+        impl ::core::fmt::Debug for InlinedJSONKey {
+            fn fmt(&self, f: &mut ::core::fmt::Formatter) -> ::core::fmt::Result {
+                ::core::fmt::Debug::fmt(InlinedJSONKey::KEY, f)
             }
         }
 
-        InlinedJSONObjectEntry {
-            value: $value,
-            next: $next,
+        impl $crate::string::StaticJSONKey for InlinedJSONKey {
+            const KEY: &'static str = InlinedJSONKey::KEY;
+            const FIRST_PREFIX: &'static [u8] = &InlinedJSONKey::FIRST_BYTES;
+            const NEXT_PREFIX: &'static [u8] = &InlinedJSONKey::NEXT_BYTES;
         }
+
+        InlinedJSONKey
     }};
 }
 
+/// Creates a zero-sized json string from an identifier or a string literal,
+/// escaped at compile time. It implements [`StaticJSONKey`](string/trait.StaticJSONKey.html),
+/// to be used with [`JSONObject::with_static`](object/trait.JSONObject.html#method.with_static).
+/// Raw identifiers lose their `r#` prefix.
+///
+/// # Examples
+/// ```
+/// use json_in_type::*;
+///
+/// assert_eq!(r#""type""#, json_key!(r#type).to_json_string());
+/// assert_eq!(r#""a\"b""#, json_key!("a\"b").to_json_string());
+/// assert_eq!(0, std::mem::size_of_val(&json_key!(x)));
+/// ```
+#[macro_export]
+macro_rules! json_key {
+    ($key:ident) => { inlined_json_key!($key) };
+    ($key:literal) => { inlined_json_key!(key_str: $key) };
+}
+
 /// Creates a static json object that can be serialized very fast.
 /// Returns a struct implementing [`JSONValue`](trait.JSONValue.html).
 ///
 /// The macro takes a comma-separated list of key-value pairs.
-/// Keys can be written literally, or surrounded by brackets (`[key]`)
-/// to reference external variables. A value can be omitted, in which
+/// Keys can be written literally, as identifiers or string literals,
+/// or surrounded by brackets (`[key]`) to reference external variables. A value can be omitted, in which
 /// case the the key name must be the name of a variable currently in scope,
 /// from which the value will be taken.
 ///
@@ -216,6 +1633,20 @@ macro_rules! inlined_json_object {
 /// assert_eq!(r#"{"x":"hello","y":42,"z":true}"#, my_obj.to_json_string());
 /// ```
 ///
+/// ### Keys that are not identifiers
+/// String literal keys are escaped at compile time, and cost nothing more than identifiers.
+/// Raw identifiers lose their `r#` prefix.
+/// ```
+/// use json_in_type::*;
+///
+/// let my_obj = json_object!{
+///     "content-type": "text/html",
+///     r#type: "page",
+/// };
+///
+/// assert_eq!(r#"{"content-type":"text/html","type":"page"}"#, my_obj.to_json_string());
+/// ```
+///
 /// ### Reference external variables
 /// ```
 /// use json_in_type::*;
@@ -240,13 +1671,160 @@ macro_rules! inlined_json_object {
 ///
 /// assert_eq!(r#"{"hello":"world","hello_suffix":42}"#, my_obj.to_json_string());
 /// ```
+///
+/// ### Optional and conditional keys
+/// A key followed by a question mark takes an `Option` as value,
+/// and is omitted when it is `None`.
+/// A key preceded by `if condition =>` is omitted when the condition is false,
+/// in which case its value is not evaluated.
+/// ```
+/// use json_in_type::*;
+///
+/// let nickname: Option<&str> = None;
+/// let age = Some(42);
+/// let admin = false;
+/// let my_obj = json_object!{
+///     name: "Ada",
+///     nickname?: nickname,
+///     age?,
+///     if admin => permissions: vec!["all"],
+///     if !admin => ["read" /* computed keys work too */]: true,
+/// };
+///
+/// assert_eq!(r#"{"name":"Ada","age":42,"read":true}"#, my_obj.to_json_string());
+/// ```
+///
+/// ### Spread the members of another object
+/// `..other` inserts all the members of `other`, that can be another static object,
+/// a derived struct, a map or a [`ToJSONObject`](object/struct.ToJSONObject.html).
+/// Keys are not deduplicated.
+/// ```
+/// use json_in_type::*;
+/// use std::collections::BTreeMap;
+///
+/// let base = json_object!{ version: 2, ok: true };
+/// let mut extra = BTreeMap::new();
+/// extra.insert("trace", "abc");
+/// let my_obj = json_object!{ ..&base, id: 7, ..extra };
+///
+/// assert_eq!(r#"{"version":2,"ok":true,"id":7,"trace":"abc"}"#, my_obj.to_json_string());
+/// ```
+///
+/// ### Reuse an object
+/// The values of an object can be modified in place with
+/// [`json_field_mut!`](macro.json_field_mut.html), to serialize it again.
+///
+/// ### Large objects
+/// Each member of a `json_object!` has its own type, nested in the type of the previous member.
+/// For objects with hundreds of members, that are slow to compile, the
+/// [`flat_json_object!`](https://docs.rs/json_in_type_derive/latest/json_in_type_derive/macro.flat_json_object.html)
+/// macro from json_in_type_derive accepts the same syntax, without optional keys,
+/// conditional keys and spreads, and generates a single struct.
 #[macro_export]
 macro_rules! json_object {
     () => { $crate::object::JSONObjectEnd{} };
-    // A null value
-    ($key:ident : null, $($rest:tt)*) => { json_object!($key : (), $($rest)*) };
-    ($key:ident : true, $($rest:tt)*) => { json_object!($key : $crate::base_types::JSONtrue, $($rest)*) };
-    ($key:ident : false, $($rest:tt)*) => { json_object!($key : $crate::base_types::JSONfalse, $($rest)*) };
+    // A conditional key
+    (if $cond:expr => $key:ident : $value:expr, $($rest:tt)*) => {
+        json_object!($key ?: if $cond { ::core::option::Option::Some($value) } else { ::core::option::Option::None }, $($rest)*)
+    };
+    (if $cond:expr => $key:literal : $value:expr, $($rest:tt)*) => {
+        json_object!($key ?: if $cond { ::core::option::Option::Some($value) } else { ::core::option::Option::None }, $($rest)*)
+    };
+    (if $cond:expr => [$key:expr] : $value:expr, $($rest:tt)*) => {
+        json_object!([$key] ?: if $cond { ::core::option::Option::Some($value) } else { ::core::option::Option::None }, $($rest)*)
+    };
+    // An optional key
+    ($key:ident ? : $value:expr, $($rest:tt)*) => {
+        $crate::object::StaticOptionalJSONObjectEntry {
+            key: inlined_json_key!($key),
+            value: $value,
+            next: json_object!($($rest)*)
+        }
+    };
+    ($key:literal ? : $value:expr, $($rest:tt)*) => {
+        $crate::object::StaticOptionalJSONObjectEntry {
+            key: inlined_json_key!(key_str: $key),
+            value: $value,
+            next: json_object!($($rest)*)
+        }
+    };
+    ([$key:expr] ? : $value:expr, $($rest:tt)*) => {
+        $crate::object::OptionalJSONObjectEntry {
+            key: $key,
+            value: $value,
+            next: json_object!($($rest)*)
+        }
+    };
+    ($key:ident ?, $($rest:tt)*) => { json_object!($key ?: $key, $($rest)*) };
+    // The members of another object
+    (.. $other:expr, $($rest:tt)*) => {
+        $crate::object::JSONObjectSpread {
+            object: $other,
+            next: json_object!($($rest)*)
+        }
+    };
+    // The key is an expression in square brackets
+    ([$key:expr] : $value:expr $(, $($rest:tt)*)?) => {
+        $crate::object::JSONObjectEntry {
+            key: $key,
+            value: $value,
+            next: json_object!($($($rest)*)?)
+        }
+    };
+    // Consecutive members with constant values are serialized together at compile time
+    ($key:tt : null $(, $($rest:tt)*)?) => { json_object!(@const [] $key : null $(, $($rest)*)?) };
+    ($key:tt : true $(, $($rest:tt)*)?) => { json_object!(@const [] $key : true $(, $($rest)*)?) };
+    ($key:tt : false $(, $($rest:tt)*)?) => { json_object!(@const [] $key : false $(, $($rest)*)?) };
+    ($key:tt : json_object ! $body:tt $(, $($rest:tt)*)?) => { json_object!(@const [] $key : json_object ! $body $(, $($rest)*)?) };
+    ($key:tt : json_list ! $body:tt $(, $($rest:tt)*)?) => { json_object!(@const [] $key : json_list ! $body $(, $($rest)*)?) };
+    (@const [$($members:tt)*] $key:ident : $value:tt $(! $body:tt)? $(, $($rest:tt)*)?) => {
+        json_object!(@const_value [$($members)*] ($crate::string::unraw_identifier(stringify!($key))) ($value $(! $body)?) $($($rest)*)?)
+    };
+    (@const [$($members:tt)*] $key:literal : $value:tt $(! $body:tt)? $(, $($rest:tt)*)?) => {
+        json_object!(@const_value [$($members)*] ($key) ($value $(! $body)?) $($($rest)*)?)
+    };
+    (@const [$($members:tt)+]) => { inlined_json_fragment!(object: [$($members)+]) };
+    (@const [$($members:tt)+] $($rest:tt)+) => {
+        inlined_json_fragment!(object: [$($members)+], next: json_object!($($rest)+))
+    };
+    (@const_value [$($members:tt)*] ($key:expr) (null) $($rest:tt)*) => {
+        json_object!(@const [$($members)* ($key, ())] $($rest)*)
+    };
+    (@const_value [$($members:tt)*] ($key:expr) (true) $($rest:tt)*) => {
+        json_object!(@const [$($members)* ($key, $crate::base_types::JSONtrue)] $($rest)*)
+    };
+    (@const_value [$($members:tt)*] ($key:expr) (false) $($rest:tt)*) => {
+        json_object!(@const [$($members)* ($key, $crate::base_types::JSONfalse)] $($rest)*)
+    };
+    (@const_value [$($members:tt)*] ($key:expr) (json_object ! $body:tt) $($rest:tt)*) => {
+        json_object!(@if_empty $body
+            { json_object!(@const [$($members)* ($key, $crate::object::JSONObjectEnd{})] $($rest)*) }
+            { json_object!(@const_end [$($members)*] ($key) json_object! $body, $($rest)*) })
+    };
+    (@const_value [$($members:tt)*] ($key:expr) (json_list ! $body:tt) $($rest:tt)*) => {
+        json_object!(@if_empty $body
+            { json_object!(@const [$($members)* ($key, $crate::list::JSONListEnd{})] $($rest)*) }
+            { json_object!(@const_end [$($members)*] ($key) json_list! $body, $($rest)*) })
+    };
+    (@const_value [$($members:tt)*] ($key:expr) ($($value:tt)*) $($rest:tt)*) => {
+        json_object!(@const_end [$($members)*] ($key) $($value)*, $($rest)*)
+    };
+    // A member whose value turned out not to be constant ends the run
+    (@const_end [] ($key:expr) $value:expr, $($rest:tt)*) => {
+        inlined_json_object!{
+            key_str: $key,
+            value: $value,
+            next: json_object!($($rest)*)
+         }
+    };
+    (@const_end [$($members:tt)+] ($key:expr) $value:expr, $($rest:tt)*) => {
+        inlined_json_fragment!(object: [$($members)+], next: json_object!(@const_end [] ($key) $value, $($rest)*))
+    };
+    // Whether a macro invocation has no arguments, whatever its delimiters
+    (@if_empty {} { $($then:tt)* } $else:tt) => { $($then)* };
+    (@if_empty () { $($then:tt)* } $else:tt) => { $($then)* };
+    (@if_empty [] { $($then:tt)* } $else:tt) => { $($then)* };
+    (@if_empty $body:tt $then:tt { $($else:tt)* }) => { $($else)* };
     // Literal key
     ($key:ident : $value:expr, $($rest:tt)*) => {
         inlined_json_object!{
@@ -255,27 +1833,222 @@ macro_rules! json_object {
             next: json_object!($($rest)*)
          }
     };
-    // The key is an expression in square brackets
-    ([$key:expr] : $value:expr, $($rest:tt)*) => {
-        $crate::object::JSONObjectEntry {
-            key: $key,
+    // String literal key, escaped at compile time
+    ($key:literal : $value:expr, $($rest:tt)*) => {
+        inlined_json_object!{
+            key_str: $key,
             value: $value,
             next: json_object!($($rest)*)
-        }
+         }
     };
     // A key that references a variable of the same name
     ($key:ident, $($rest:tt)*) => { json_object!($key : $key, $($rest)*) };
     // Simply adding a trailing colon
     ($key:ident : $value:ident) => { json_object!($key:$value,) };
     ($key:ident : $value:expr) => { json_object!($key:$value,) };
-    ([$key:expr] : $value:expr) => { json_object!([$key]:$value,) };
+    ($key:literal : $value:ident) => { json_object!($key:$value,) };
+    ($key:literal : $value:expr) => { json_object!($key:$value,) };
     ($key:ident) => { json_object!($key,) };
+    (if $cond:expr => $key:ident : $value:expr) => { json_object!(if $cond => $key:$value,) };
+    (if $cond:expr => $key:literal : $value:expr) => { json_object!(if $cond => $key:$value,) };
+    (if $cond:expr => [$key:expr] : $value:expr) => { json_object!(if $cond => [$key]:$value,) };
+    ($key:ident ? : $value:expr) => { json_object!($key ?: $value,) };
+    ($key:literal ? : $value:expr) => { json_object!($key ?: $value,) };
+    ([$key:expr] ? : $value:expr) => { json_object!([$key] ?: $value,) };
+    ($key:ident ?) => { json_object!($key ?,) };
+    (.. $other:expr) => { json_object!(..$other,) };
+}
+
+/// Declares a named type for the objects of a given shape,
+/// that can be stored in struct fields and returned from functions.
+/// Returns nothing: it is used as an item, like a struct definition.
+///
+/// The members are listed with their keys, identifiers or string literals as in
+/// [`json_object!`](macro.json_object.html), followed by the type of their values.
+/// The keys are escaped at compile time, and take no space in memory.
+/// The type is created from a tuple of values, in the order of the keys,
+/// and implements [`JSONObject`](object/trait.JSONObject.html).
+///
+/// Attributes, such as derives, are applied to the generated struct.
+///
+/// # Examples
+/// ```
+/// use json_in_type::*;
+///
+/// json_type! {
+///     /// The response of a health check
+///     #[derive(Clone, Debug)]
+///     pub struct Health {
+///         status: &'static str,
+///         "uptime-seconds": u64,
+///     }
+/// }
+///
+/// struct Server {
+///     last_health: Health,
+/// }
+///
+/// let server = Server { last_health: Health::from(("ok", 42)) };
+/// let health = server.last_health.clone();
+/// assert_eq!(r#"{"status":"ok","uptime-seconds":42}"#, health.to_json_string());
+/// ```
+#[macro_export]
+macro_rules! json_type {
+    (@key_str $key:ident) => { $crate::string::unraw_identifier(stringify!($key)) };
+    (@key_str $key:literal) => { $key };
+    // The type of the members, each key identified by its position
+    (@chain $name:ident [$($index:tt)*] $key:tt : $value:ty, $($rest:tt)*) => {
+        $crate::object::StaticJSONObjectEntry<
+            $crate::object::NamedJSONKey<$name, { 0 $($index)* }>,
+            $value,
+            json_type!(@chain $name [$($index)* + 1] $($rest)*)
+        >
+    };
+    (@chain $name:ident [$($index:tt)*]) => { $crate::object::JSONObjectEnd };
+    // The keys, escaped at compile time
+    (@keys $name:ident [$($index:tt)*] $key:tt, $($rest:tt)*) => {
+        impl $crate::object::JSONTypeKey<{ 0 $($index)* }> for $name {
+            const KEY: &'static str = json_type!(@key_str $key);
+            const FIRST_PREFIX: &'static [u8] = &$crate::string::member_prefix::<
+                { $crate::string::member_prefix_len(json_type!(@key_str $key)) },
+            >(json_type!(@key_str $key), b'{');
+            const NEXT_PREFIX: &'static [u8] = &$crate::string::member_prefix::<
+                { $crate::string::member_prefix_len(json_type!(@key_str $key)) },
+            >(json_type!(@key_str $key), b',');
+        }
+        json_type!(@keys $name [$($index)* + 1] $($rest)*);
+    };
+    (@keys $name:ident [$($index:tt)*]) => {};
+    // Names each value of the tuple, then builds the members from them
+    (@from $values:ident [$($named:ident)*] $key:tt, $($rest:tt)*) => {
+        json_type!(@from $values [$($named)* value] $($rest)*)
+    };
+    (@from $values:ident [$($named:ident)*]) => {{
+        let ($($named,)*) = $values;
+        json_type!(@members $($named)*)
+    }};
+    (@members $value:ident $($rest:ident)*) => {
+        $crate::object::StaticJSONObjectEntry {
+            key: $crate::object::NamedJSONKey::new(),
+            value: $value,
+            next: json_type!(@members $($rest)*),
+        }
+    };
+    (@members) => { $crate::object::JSONObjectEnd };
+    ($(#[$attr:meta])* $vis:vis struct $name:ident { $($key:tt : $value:ty),* $(,)? }) => {
+        $(#[$attr])*
+        $vis struct $name(json_type!(@chain $name [] $($key : $value,)*));
+
+        json_type!(@keys $name [] $($key,)*);
+
+        impl ::core::convert::From<($($value,)*)> for $name {
+            fn from(values: ($($value,)*)) -> $name {
+                $name(json_type!(@from values [] $($key,)*))
+            }
+        }
+
+        impl $crate::JSONValue for $name {
+            #[inline(always)]
+            fn write_json<W: $crate::write::JSONWrite>(
+                &self,
+                w: &mut W,
+            ) -> ::core::result::Result<(), W::Error> {
+                $crate::JSONValue::write_json(&self.0, w)
+            }
+
+            fn write_json_formatted<W: $crate::write::JSONWrite>(
+                &self,
+                w: &mut W,
+                f: &mut $crate::format::JSONFormatter,
+            ) -> ::core::result::Result<(), W::Error> {
+                $crate::JSONValue::write_json_formatted(&self.0, w, f)
+            }
+
+            fn json_len(&self) -> Option<usize> {
+                $crate::JSONValue::json_len(&self.0)
+            }
+        }
+
+        impl $crate::object::JSONObject for $name {
+            #[inline(always)]
+            fn write_json_ending<W: $crate::write::JSONWrite>(
+                &self,
+                w: &mut W,
+                first: bool,
+            ) -> ::core::result::Result<(), W::Error> {
+                $crate::object::JSONObject::write_json_ending(&self.0, w, first)
+            }
+
+            fn write_json_ending_formatted<W: $crate::write::JSONWrite>(
+                &self,
+                w: &mut W,
+                f: &mut $crate::format::JSONFormatter,
+                first: bool,
+            ) -> ::core::result::Result<(), W::Error> {
+                $crate::object::JSONObject::write_json_ending_formatted(&self.0, w, f, first)
+            }
+
+            fn json_len_ending(&self, first: bool) -> Option<usize> {
+                $crate::object::JSONObject::json_len_ending(&self.0, first)
+            }
+        }
+
+        impl $crate::object::ObjectEntries for $name {
+            fn visit_entries<E: $crate::object::EntryVisitor>(
+                &self,
+                visitor: &mut E,
+            ) -> ::core::result::Result<(), E::Error> {
+                $crate::object::ObjectEntries::visit_entries(&self.0, visitor)
+            }
+        }
+    };
+}
+
+/// Returns a mutable reference to the value of a member of a static object,
+/// created by [`json_object!`](macro.json_object.html), given its key.
+///
+/// This allows to reuse an object as a template, changing only some of its values,
+/// instead of building it again.
+/// The member is found at compile time, from an identifier or a string literal key,
+/// and the reference has the exact type of the value: `Option<T>` for optional keys.
+/// Keys given as `[json_key!(...)]` can be found too.
+///
+/// Compilation fails if the key is missing, or present more than once.
+/// Members whose value is a constant (`null`, `true`, `false`, or an empty `json_object!` or `json_list!`)
+/// are serialized at compile time,
+/// and cannot be modified; neither can members inserted with `..other`.
+///
+/// # Examples
+/// ```
+/// use json_in_type::*;
+///
+/// let mut point = json_object! { x: 0, "y-coord": 0, label?: None, visible: true };
+/// let mut lines = vec![];
+/// for i in 1..=2 {
+///     *json_field_mut!(point, x) = i;
+///     *json_field_mut!(point, "y-coord") = 10 * i;
+///     *json_field_mut!(point, label) = Some("last").filter(|_| i == 2);
+///     lines.push(point.to_json_string());
+/// }
+/// assert_eq!(r#"{"x":1,"y-coord":10,"visible":true}"#, lines[0]);
+/// assert_eq!(r#"{"x":2,"y-coord":20,"label":"last","visible":true}"#, lines[1]);
+/// ```
+#[macro_export]
+macro_rules! json_field_mut {
+    ($object:expr, $key:ident) => {
+        json_field_mut!(@hash $object, $crate::string::unraw_identifier(stringify!($key)))
+    };
+    ($object:expr, $key:literal) => { json_field_mut!(@hash $object, $key) };
+    (@hash $object:expr, $key:expr) => {
+        $crate::object::FieldMut::<{ $crate::string::key_hash($key) }, _>::field_mut(&mut $object)
+    };
 }
 
 #[cfg(test)]
 mod tests {
     // Note this useful idiom: importing names from outer (for mod tests) scope.
     use super::*;
+    use crate::*;
 
     #[test]
     fn test_empty() {
@@ -336,16 +2109,164 @@ mod tests {
         );
     }
 
+    #[cfg(feature = "std")]
     #[test]
     fn test_hashmap() {
         let mut map = HashMap::new();
         map.insert("x", 1);
         map.insert("y", 2);
         // The order in which the keys are serialized is not guaranteed
-        let expected = vec![r#"{"x":1,"y":2}"#, r#"{"y":2,"x":1}"#];
+        let expected = [r#"{"x":1,"y":2}"#, r#"{"y":2,"x":1}"#];
         assert!(expected.contains(&&map.to_json_string()[..]));
     }
 
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_sorted_hashmap() {
+        let map: HashMap<String, u32> = (0..100).map(|i| (format!("k{}", i), i)).collect();
+        let expected: BTreeMap<_, _> = map.clone().into_iter().collect();
+        let expected = expected.to_json_string();
+        assert_eq!(expected, Sorted(&map).to_json_string());
+        assert_eq!(Some(expected.len()), Sorted(&map).json_len());
+        assert_eq!(expected, Sorted(map).to_json_string());
+        let empty: HashMap<&str, ()> = HashMap::new();
+        assert_eq!("{}", Sorted(&empty).to_json_string());
+        assert_eq!("{}", Sorted(&empty).to_json_string_pretty());
+    }
+
+    #[test]
+    fn test_btreemap() {
+        let map: BTreeMap<_, _> = vec![("b", vec![2]), ("a", vec![])].into_iter().collect();
+        assert_eq!(r#"{"a":[],"b":[2]}"#, map.to_json_string());
+        assert_eq!(Some(map.to_json_string().len()), map.json_len());
+        assert_eq!(
+            "{\n  \"a\": [],\n  \"b\": [\n    2\n  ]\n}",
+            map.to_json_string_pretty()
+        );
+    }
+
+    #[cfg(feature = "indexmap")]
+    #[test]
+    fn test_indexmap() {
+        let mut map = IndexMap::new();
+        map.insert("z", 1);
+        map.insert("a", 2);
+        assert_eq!(r#"{"z":1,"a":2}"#, map.to_json_string());
+        assert_eq!(Some(map.to_json_string().len()), map.json_len());
+    }
+
+    #[test]
+    fn test_len() {
+        fn check<T: JSONValue>(value: T) {
+            assert_eq!(Some(value.to_json_string().len()), value.json_len());
+        }
+        let key = "k\"ey";
+        check(json_object!());
+        check(json_object!(x: 1));
+        check(json_object!(x: 1, [key]: "value", y: json_object!(z: null)));
+        check(ToJSONObject(Vec::<(&str, u8)>::new()));
+        check(ToJSONObject(vec![("x", 1), ("y", 22)]));
+        #[cfg(feature = "std")]
+        {
+            let mut map = HashMap::new();
+            check(&map);
+            map.insert("x", vec![1]);
+            map.insert("y", vec![]);
+            check(&map);
+        }
+    }
+
+    #[test]
+    fn test_pretty() {
+        assert_eq!("{}", json_object!().to_json_string_pretty());
+        let x = "x";
+        let obj = json_object! {
+            [x]: 1,
+            y: json_object!{ z: null },
+            list: ToJSONObject(vec![("a", true)])
+        };
+        assert_eq!(
+            "{\n  \"x\": 1,\n  \"y\": {\n    \"z\": null\n  },\n  \"list\": {\n    \"a\": true\n  }\n}",
+            obj.to_json_string_pretty()
+        );
+        let map: BTreeMap<_, _> = vec![("x", vec![1])].into_iter().collect();
+        assert_eq!("{\n  \"x\": [\n    1\n  ]\n}", map.to_json_string_pretty());
+    }
+
+    #[test]
+    fn test_spread() {
+        fn check<T: JSONValue>(expected: &str, value: T) {
+            assert_eq!(expected, value.to_json_string());
+            assert_eq!(Some(expected.len()), value.json_len());
+        }
+        let empty: Vec<(&str, u8)> = vec![];
+        check("{}", json_object! { ..json_object!{} });
+        check(
+            "{}",
+            json_object! { ..ToJSONObject(empty.clone()), ..json_object!{} },
+        );
+        check(
+            r#"{"a":1}"#,
+            json_object! { ..ToJSONObject(empty.clone()), a: 1, ..json_object!{} },
+        );
+        check(
+            r#"{"x":1,"y":2,"a":3}"#,
+            json_object! { ..json_object!{ x: 1, y: 2 }, a: 3 },
+        );
+        check(
+            r#"{"a":3,"x":1,"y":2,"b":{}}"#,
+            json_object! { a: 3, ..ToJSONObject(vec![("x", 1), ("y", 2)]), b: json_object!{} },
+        );
+        let merged = json_object! { y: 1 }
+            .merge(json_object! { x: 2 })
+            .merge(BTreeMap::<&str, ()>::new());
+        check(r#"{"y":1,"x":2}"#, &merged);
+        assert_eq!(
+            "{\n  \"y\": 1,\n  \"x\": 2\n}",
+            merged.to_json_string_pretty()
+        );
+        let mut sorted = vec![];
+        merged
+            .write_json_formatted(&mut sorted, &mut JSONFormatter::compact().sort_keys(true))
+            .unwrap();
+        assert_eq!(br#"{"x":2,"y":1}"#.to_vec(), sorted);
+        let empty_spread = json_object! { ..ToJSONObject(empty) };
+        assert_eq!("{}", empty_spread.to_json_string_pretty());
+    }
+
+    #[test]
+    fn test_builder() {
+        fn check<T: JSONValue>(expected: &str, value: T) {
+            assert_eq!(expected, value.to_json_string());
+            assert_eq!(Some(expected.len()), value.json_len());
+        }
+        check(r#"{"a":1}"#, JSONObjectEnd.with_key("a", 1));
+        check(
+            r#"{"a\"":1}"#,
+            JSONObjectEnd.with_static(json_key!("a\""), 1),
+        );
+        let x = 2;
+        let obj = json_object! { a: null, b: x }
+            .with_key(String::from("c"), json_object! { d: true })
+            .with_static(json_key!(r#type), x)
+            .merge(json_object! {})
+            .with_static(json_key!("e-f"), json_list![]);
+        check(r#"{"a":null,"b":2,"c":{"d":true},"type":2,"e-f":[]}"#, &obj);
+        assert_eq!(
+            "{\n  \"a\": null,\n  \"b\": 2,\n  \"c\": {\n    \"d\": true\n  },\n  \"type\": 2,\n  \"e-f\": []\n}",
+            obj.to_json_string_pretty()
+        );
+        let mut sorted = vec![];
+        JSONObjectEnd
+            .with_static(json_key!(z), 1)
+            .with_key("y", 2)
+            .write_json_formatted(&mut sorted, &mut JSONFormatter::compact().sort_keys(true))
+            .unwrap();
+        assert_eq!(br#"{"y":2,"z":1}"#.to_vec(), sorted);
+        let zero_sized = json_object! { a: true }.with_static(json_key!(b), ());
+        assert_eq!(0, std::mem::size_of_val(&zero_sized));
+    }
+
     #[test]
     fn test_zero_size() {
         use std::mem::size_of_val;
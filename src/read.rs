@@ -0,0 +1,348 @@
+//! Pull-based serialization, through [`std::io::Read`](https://doc.rust-lang.org/std/io/trait.Read.html)
+//!
+//! [`write_json`](../trait.JSONValue.html#tymethod.write_json) pushes the whole
+//! document to its writer at once. Some APIs, such as HTTP clients and servers
+//! with backpressure, instead want to pull the bytes of a request or response body
+//! when they are ready to send them.
+//! A [`JSONReader`](struct.JSONReader.html) produces the JSON representation
+//! of a value chunk by chunk, as it is read.
+//!
+//! This module is only available with the `std` feature.
+
+use super::JSONValue;
+use std::io;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, sync_channel, Receiver, SyncSender};
+use std::sync::{Mutex, OnceLock};
+use std::thread;
+use std::time::Duration;
+
+/// The default size of the chunks produced by a [`JSONReader`](struct.JSONReader.html), in bytes
+pub const DEFAULT_CHUNK_SIZE: usize = 8 * 1024;
+
+/// Implements [`io::Read`](https://doc.rust-lang.org/std/io/trait.Read.html)
+/// by serializing a [`JSONValue`](../trait.JSONValue.html) on demand.
+///
+/// The value is serialized in a background thread, that pauses
+/// as soon as it has produced a chunk that has not been read yet.
+/// The threads are taken from a pool shared by all the readers,
+/// and wait for another reader once they are done.
+/// Streaming values, such as iterators wrapped in a `RefCell`,
+/// are thus consumed only as the JSON is read,
+/// and the whole document is never held in memory.
+/// If the reader is dropped before the end of the document,
+/// the serialization is stopped.
+///
+/// # Costs and limitations
+///
+/// A reader occupies a thread of the pool until its value is serialized or it is dropped,
+/// so there are as many threads as readers in progress at the same time.
+/// Threads that have been idle for a minute exit.
+/// Every chunk is handed from one thread to the other. This is negligible for large documents,
+/// but expensive compared to serializing a small value to a buffer,
+/// so small response bodies are better written with
+/// [`to_json_buffer`](../trait.JSONValue.html#method.to_json_buffer).
+///
+/// Since the value is moved to another thread, it has to be `Send + 'static`,
+/// and cannot borrow local data: a `json_object!` that refers to local variables
+/// has to take ownership of them, for instance by cloning them.
+///
+/// If the serialization fails or panics, the next read returns an error,
+/// and so do all the reads after it.
+///
+/// # Examples
+///
+/// ```
+/// use std::cell::RefCell;
+/// use std::io::Read;
+/// use json_in_type::*;
+/// use json_in_type::read::JSONReader;
+///
+/// let lines = (1..=1000).map(|line| json_object! { line });
+/// let value = json_object! { lines: RefCell::new(lines) };
+/// let mut reader = JSONReader::with_chunk_size(16, value);
+///
+/// let mut start = [0; 20];
+/// reader.read_exact(&mut start).unwrap();
+/// assert_eq!(br#"{"lines":[{"line":1}"#, &start);
+/// ```
+pub struct JSONReader {
+    chunks: Receiver<io::Result<Vec<u8>>>,
+    chunk: Vec<u8>,
+    position: usize,
+    /// The kind of the error returned by the serialization, if any
+    failed: Option<io::ErrorKind>,
+}
+
+impl JSONReader {
+    /// Creates a reader that produces chunks of
+    /// [`DEFAULT_CHUNK_SIZE`](constant.DEFAULT_CHUNK_SIZE.html) bytes
+    pub fn new<T: JSONValue + Send + 'static>(value: T) -> JSONReader {
+        JSONReader::with_chunk_size(DEFAULT_CHUNK_SIZE, value)
+    }
+
+    /// Creates a reader that produces chunks of approximately `chunk_size` bytes
+    pub fn with_chunk_size<T: JSONValue + Send + 'static>(
+        chunk_size: usize,
+        value: T,
+    ) -> JSONReader {
+        // A rendezvous channel: the serializer waits for each chunk to be read
+        let (sender, chunks) = sync_channel(0);
+        pool().execute(Box::new(move || {
+            let mut writer = ChunkWriter {
+                sender,
+                chunk: Vec::with_capacity(chunk_size),
+                chunk_size,
+            };
+            let result = catch_unwind(AssertUnwindSafe(|| value.write_json(&mut writer)))
+                .unwrap_or_else(|_panic| Err(io::Error::other("the serialization panicked")));
+            // If sending fails, the reader has been dropped, and nobody is interested in the result
+            let _ = match result {
+                Ok(()) => writer.sender.send(Ok(writer.chunk)),
+                Err(err) => writer.sender.send(Err(err)),
+            };
+        }));
+        JSONReader {
+            chunks,
+            chunk: vec![],
+            position: 0,
+            failed: None,
+        }
+    }
+}
+
+impl io::Read for JSONReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if let Some(kind) = self.failed {
+            return Err(io::Error::new(kind, "the serialization failed"));
+        }
+        while self.position == self.chunk.len() {
+            match self.chunks.recv() {
+                Ok(Ok(chunk)) => {
+                    self.chunk = chunk;
+                    self.position = 0;
+                }
+                Ok(Err(err)) => {
+                    self.failed = Some(err.kind());
+                    return Err(err);
+                }
+                // The serializer sent its last chunk
+                Err(_disconnected) => return Ok(0),
+            }
+        }
+        let available = &self.chunk[self.position..];
+        let size = available.len().min(buf.len());
+        buf[..size].copy_from_slice(&available[..size]);
+        self.position += size;
+        Ok(size)
+    }
+}
+
+/// A serialization to run in the background
+type Job = Box<dyn FnOnce() + Send>;
+
+/// How long an idle thread of the pool waits for another reader before exiting
+const IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// The threads that serialize the values of readers
+struct Pool {
+    sender: mpsc::Sender<Job>,
+    jobs: Mutex<Receiver<Job>>,
+    /// The number of threads waiting for a job that nobody has claimed yet
+    idle: AtomicUsize,
+}
+
+fn pool() -> &'static Pool {
+    static POOL: OnceLock<Pool> = OnceLock::new();
+    POOL.get_or_init(|| {
+        let (sender, jobs) = mpsc::channel();
+        Pool {
+            sender,
+            jobs: Mutex::new(jobs),
+            idle: AtomicUsize::new(0),
+        }
+    })
+}
+
+impl Pool {
+    /// Claims an idle thread, if there is one
+    fn claim_idle(&self) -> bool {
+        self.idle
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |idle| {
+                idle.checked_sub(1)
+            })
+            .is_ok()
+    }
+
+    /// Runs a job on an idle thread, or on a new one if all the threads are busy.
+    /// Jobs must not panic.
+    fn execute(&'static self, job: Job) {
+        if !self.claim_idle() {
+            thread::spawn(move || self.work());
+        }
+        self.sender.send(job).expect("the pool is never dropped");
+    }
+
+    fn work(&self) {
+        loop {
+            let job = self.jobs.lock().unwrap().recv_timeout(IDLE_TIMEOUT);
+            match job {
+                Ok(job) => {
+                    job();
+                    self.idle.fetch_add(1, Ordering::SeqCst);
+                }
+                // If the thread has been claimed in the meantime, a job is on its way
+                Err(_timeout) if self.claim_idle() => return,
+                Err(_timeout) => {}
+            }
+        }
+    }
+}
+
+/// Sends the bytes written to it to a [`JSONReader`](struct.JSONReader.html), by chunks
+struct ChunkWriter {
+    sender: SyncSender<io::Result<Vec<u8>>>,
+    chunk: Vec<u8>,
+    chunk_size: usize,
+}
+
+impl io::Write for ChunkWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.chunk.extend_from_slice(buf);
+        if self.chunk.len() >= self.chunk_size {
+            let next_chunk = Vec::with_capacity(self.chunk_size);
+            let chunk = std::mem::replace(&mut self.chunk, next_chunk);
+            self.sender
+                .send(Ok(chunk))
+                .map_err(|_| io::Error::from(io::ErrorKind::BrokenPipe))?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+    use crate::error::JSONError;
+    use crate::write::JSONWrite;
+    use crate::*;
+    use std::cell::RefCell;
+    use std::io::Read;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    #[test]
+    fn test_read_to_end() {
+        let value = || json_object! { x: vec!["hello"; 100], y: null };
+        let mut result = vec![];
+        JSONReader::with_chunk_size(7, value())
+            .read_to_end(&mut result)
+            .unwrap();
+        assert_eq!(value().to_json_buffer(), result);
+    }
+
+    #[test]
+    fn test_small_reads() {
+        let mut reader = JSONReader::with_chunk_size(4, vec![1, 2, 3, 4, 5]);
+        let mut result = vec![];
+        let mut buf = [0; 3];
+        loop {
+            let size = reader.read(&mut buf).unwrap();
+            if size == 0 {
+                break;
+            }
+            result.extend_from_slice(&buf[..size]);
+        }
+        assert_eq!(b"[1,2,3,4,5]".to_vec(), result);
+    }
+
+    #[test]
+    fn test_incremental() {
+        let produced = Arc::new(AtomicUsize::new(0));
+        let counter = produced.clone();
+        let values = (0..10_000).inspect(move |_| {
+            counter.fetch_add(1, Ordering::SeqCst);
+        });
+        let mut reader = JSONReader::with_chunk_size(10, RefCell::new(values));
+        let mut start = [0; 10];
+        reader.read_exact(&mut start).unwrap();
+        assert_eq!(b"[0,1,2,3,4", &start);
+        // The serializer can only be one chunk ahead of the reader
+        assert!(produced.load(Ordering::SeqCst) < 20);
+    }
+
+    #[test]
+    fn test_errors() {
+        struct Panics;
+
+        impl JSONValue for Panics {
+            fn write_json<W: JSONWrite>(&self, w: &mut W) -> Result<(), W::Error> {
+                w.write_all(b"[")?;
+                panic!("cannot serialize")
+            }
+        }
+
+        let mut reader = JSONReader::with_chunk_size(1, json_list![1, Panics]);
+        let mut start = [0; 3];
+        reader.read_exact(&mut start).unwrap();
+        assert_eq!(b"[1,", &start);
+        let mut rest = vec![];
+        let err = reader.read_to_end(&mut rest).unwrap_err();
+        assert_eq!(io::ErrorKind::Other, err.kind());
+        // The error does not look like the end of the document to later reads
+        assert!(reader.read(&mut start).is_err());
+
+        struct Fails;
+
+        impl JSONValue for Fails {
+            fn write_json<W: JSONWrite>(&self, _w: &mut W) -> Result<(), W::Error> {
+                Err(JSONError::NonFiniteFloat.into())
+            }
+        }
+
+        let mut reader = JSONReader::new(Fails);
+        assert_eq!(
+            io::ErrorKind::InvalidData,
+            reader.read(&mut start).unwrap_err().kind()
+        );
+    }
+
+    #[test]
+    fn test_reuses_threads() {
+        /// The id of the thread that serializes it
+        struct ThreadId;
+
+        impl JSONValue for ThreadId {
+            fn write_json<W: JSONWrite>(&self, w: &mut W) -> Result<(), W::Error> {
+                format!("{:?}", thread::current().id()).write_json(w)
+            }
+        }
+
+        let threads: std::collections::HashSet<String> = (0..10)
+            .map(|_| {
+                let mut id = String::new();
+                JSONReader::new(ThreadId).read_to_string(&mut id).unwrap();
+                // Let the thread go back to the pool
+                thread::sleep(Duration::from_millis(10));
+                id
+            })
+            .collect();
+        assert!(threads.len() < 10);
+    }
+
+    #[test]
+    fn test_drop_early() {
+        let mut reader = JSONReader::with_chunk_size(1, RefCell::new(0..));
+        let mut start = [0; 3];
+        reader.read_exact(&mut start).unwrap();
+        assert_eq!(b"[0,", &start);
+        // Dropping the reader stops the serialization of the infinite list
+        drop(reader);
+    }
+}
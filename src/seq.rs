@@ -0,0 +1,288 @@
+//! Sequences of JSON documents, written one record at a time.
+//!
+//! Log shippers and bulk-ingest endpoints often expect a stream of independent documents
+//! rather than a single list. Two formats are supported:
+//!
+//!  - [JSON Lines](https://jsonlines.org/), also known as NDJSON,
+//!    where every document is written on its own line,
+//!  - [RFC 7464](https://www.rfc-editor.org/rfc/rfc7464) JSON text sequences
+//!    (`application/json-seq`), where every document is preceded
+//!    by an ASCII record separator (`0x1E`) and followed by a line feed.
+//!
+//! Every record is serialized to an intermediate buffer before being written,
+//! so a value that fails to serialize never leaves a truncated record in the output.
+//!
+//! This module is only available with the `std` feature.
+//!
+//! # Examples
+//!
+//! ```
+//! use json_in_type::*;
+//! use json_in_type::seq::write_json_lines;
+//!
+//! let mut log: Vec<u8> = vec![];
+//! let events = (1..=2).map(|id| json_object! { id, level: "info" });
+//! let summary = write_json_lines(events, &mut log).unwrap();
+//!
+//! assert_eq!(
+//!     "{\"id\":1,\"level\":\"info\"}\n{\"id\":2,\"level\":\"info\"}\n",
+//!     String::from_utf8(log).unwrap()
+//! );
+//! assert_eq!(2, summary.records);
+//! assert_eq!(48, summary.bytes);
+//! ```
+
+use super::format::JSONFormatter;
+use super::JSONValue;
+use std::io;
+
+/// The framing of the records of a sequence
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SeqFormat {
+    /// Every record is followed by a line feed (`application/x-ndjson`, `application/jsonl`)
+    JSONLines,
+    /// Every record is preceded by a record separator and followed by a line feed,
+    /// as defined by RFC 7464 (`application/json-seq`)
+    JSONSeq,
+}
+
+impl SeqFormat {
+    /// The media type of the format
+    pub fn media_type(self) -> &'static str {
+        match self {
+            SeqFormat::JSONLines => "application/x-ndjson",
+            SeqFormat::JSONSeq => "application/json-seq",
+        }
+    }
+}
+
+/// What has been written by a [`JSONSeqWriter`](struct.JSONSeqWriter.html)
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SeqSummary {
+    /// The number of records
+    pub records: u64,
+    /// The number of bytes, including the separators
+    pub bytes: u64,
+}
+
+/// Writes JSON values as a sequence of records.
+pub struct JSONSeqWriter<W: io::Write> {
+    writer: W,
+    format: SeqFormat,
+    formatter: Option<JSONFormatter>,
+    flush_each_record: bool,
+    buffer: Vec<u8>,
+    summary: SeqSummary,
+}
+
+impl<W: io::Write> JSONSeqWriter<W> {
+    /// Creates a writer of records in the given format
+    pub fn new(format: SeqFormat, writer: W) -> JSONSeqWriter<W> {
+        JSONSeqWriter {
+            writer,
+            format,
+            formatter: None,
+            flush_each_record: false,
+            buffer: Vec::new(),
+            summary: SeqSummary::default(),
+        }
+    }
+
+    /// Creates a writer of [JSON Lines](enum.SeqFormat.html#variant.JSONLines)
+    pub fn json_lines(writer: W) -> JSONSeqWriter<W> {
+        JSONSeqWriter::new(SeqFormat::JSONLines, writer)
+    }
+
+    /// Creates a writer of [RFC 7464 records](enum.SeqFormat.html#variant.JSONSeq)
+    pub fn json_seq(writer: W) -> JSONSeqWriter<W> {
+        JSONSeqWriter::new(SeqFormat::JSONSeq, writer)
+    }
+
+    /// Serializes the records with the given formatter.
+    /// In JSON Lines, a record cannot span several lines,
+    /// so the [newline](../format/struct.JSONFormatter.html#method.newline)
+    /// of the formatter is ignored.
+    pub fn formatter(mut self, formatter: JSONFormatter) -> JSONSeqWriter<W> {
+        self.formatter = Some(match self.format {
+            SeqFormat::JSONLines => formatter.newline(""),
+            SeqFormat::JSONSeq => formatter,
+        });
+        self
+    }
+
+    /// Whether to flush the underlying writer after every record,
+    /// so that consumers receive each record as soon as it is written.
+    /// It is disabled by default.
+    pub fn flush_each_record(mut self, flush_each_record: bool) -> JSONSeqWriter<W> {
+        self.flush_each_record = flush_each_record;
+        self
+    }
+
+    /// Writes a single record
+    pub fn write_record<T: JSONValue + ?Sized>(&mut self, value: &T) -> io::Result<()> {
+        self.buffer.clear();
+        if self.format == SeqFormat::JSONSeq {
+            self.buffer.push(0x1E);
+        }
+        match &self.formatter {
+            // A failed record can leave a formatter nested, so every record gets a fresh one
+            Some(f) => value.write_json_formatted(&mut self.buffer, &mut f.clone())?,
+            None => value.write_json(&mut self.buffer)?,
+        }
+        self.buffer.push(b'\n');
+        self.writer.write_all(&self.buffer)?;
+        if self.flush_each_record {
+            self.writer.flush()?;
+        }
+        self.summary.records += 1;
+        self.summary.bytes += self.buffer.len() as u64;
+        Ok(())
+    }
+
+    /// Writes every value of an iterator as a record, as they are produced by the iterator
+    pub fn write_records<T, I>(&mut self, values: I) -> io::Result<()>
+    where
+        T: JSONValue,
+        I: IntoIterator<Item = T>,
+    {
+        values
+            .into_iter()
+            .try_for_each(|value| self.write_record(&value))
+    }
+
+    /// What has been written so far
+    #[inline]
+    pub fn summary(&self) -> SeqSummary {
+        self.summary
+    }
+
+    /// Flushes the underlying writer
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+
+    /// Returns the underlying writer
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+/// Writes every value of an iterator as a line of JSON, and flushes the writer
+pub fn write_json_lines<T, I, W>(values: I, writer: W) -> io::Result<SeqSummary>
+where
+    T: JSONValue,
+    I: IntoIterator<Item = T>,
+    W: io::Write,
+{
+    write_seq(JSONSeqWriter::json_lines(writer), values)
+}
+
+/// Writes every value of an iterator as an RFC 7464 record, and flushes the writer
+pub fn write_json_seq<T, I, W>(values: I, writer: W) -> io::Result<SeqSummary>
+where
+    T: JSONValue,
+    I: IntoIterator<Item = T>,
+    W: io::Write,
+{
+    write_seq(JSONSeqWriter::json_seq(writer), values)
+}
+
+fn write_seq<T, I, W>(mut seq: JSONSeqWriter<W>, values: I) -> io::Result<SeqSummary>
+where
+    T: JSONValue,
+    I: IntoIterator<Item = T>,
+    W: io::Write,
+{
+    seq.write_records(values)?;
+    seq.flush()?;
+    Ok(seq.summary())
+}
+
+#[cfg(test)]
+mod tests {
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+    use crate::format::NonFiniteFloats;
+    use crate::*;
+
+    /// Counts the calls to flush
+    struct Flushes {
+        written: Vec<u8>,
+        flushes: usize,
+    }
+
+    impl io::Write for Flushes {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.written.write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            self.flushes += 1;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_json_seq() {
+        let mut out = vec![];
+        let summary = write_json_seq(vec![json_list![1], json_list![2]], &mut out).unwrap();
+        assert_eq!(b"\x1e[1]\n\x1e[2]\n".to_vec(), out);
+        assert_eq!(
+            SeqSummary {
+                records: 2,
+                bytes: 10
+            },
+            summary
+        );
+        assert_eq!(
+            SeqSummary::default(),
+            write_json_seq(Vec::<u8>::new(), &mut out).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_formatter() {
+        let value = json_object! { a: json_list![f64::NAN] };
+
+        let mut lines = JSONSeqWriter::json_lines(vec![]).formatter(JSONFormatter::pretty());
+        lines.write_record(&value).unwrap();
+        assert_eq!(b"{\"a\": [null]}\n".to_vec(), lines.into_inner());
+
+        let mut seq = JSONSeqWriter::json_seq(vec![]).formatter(JSONFormatter::pretty());
+        seq.write_record(&value).unwrap();
+        assert_eq!(
+            b"\x1e{\n  \"a\": [\n    null\n  ]\n}\n".to_vec(),
+            seq.into_inner()
+        );
+
+        // A failed record is not written at all
+        let strict = JSONFormatter::compact().non_finite_floats(NonFiniteFloats::Error);
+        let mut lines = JSONSeqWriter::json_lines(vec![]).formatter(strict);
+        lines.write_record(&1).unwrap();
+        assert!(lines.write_record(&value).is_err());
+        lines.write_record(&2).unwrap();
+        assert_eq!(2, lines.summary().records);
+        assert_eq!(b"1\n2\n".to_vec(), lines.into_inner());
+
+        // Nor does it change the indentation of the following records
+        let strict = JSONFormatter::pretty().non_finite_floats(NonFiniteFloats::Error);
+        let mut seq = JSONSeqWriter::json_seq(vec![]).formatter(strict);
+        assert!(seq.write_record(&json_list![json_list![f64::NAN]]).is_err());
+        seq.write_record(&json_list![1]).unwrap();
+        assert_eq!(b"\x1e[\n  1\n]\n".to_vec(), seq.into_inner());
+    }
+
+    #[test]
+    fn test_flush_each_record() {
+        let out = Flushes {
+            written: vec![],
+            flushes: 0,
+        };
+        let mut lines = JSONSeqWriter::json_lines(out).flush_each_record(true);
+        lines.write_records(1..=3).unwrap();
+        let out = lines.into_inner();
+        assert_eq!(3, out.flushes);
+        assert_eq!(b"1\n2\n3\n".to_vec(), out.written);
+        assert_eq!(SeqFormat::JSONLines.media_type(), "application/x-ndjson");
+    }
+}
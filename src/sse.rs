@@ -0,0 +1,293 @@
+//! [Server-Sent Events](https://html.spec.whatwg.org/multipage/server-sent-events.html)
+//! framing for JSON values.
+//!
+//! An event is made of `field: value` lines, followed by an empty line.
+//! JSON values are written as the `data` field of events.
+//! A newline written by a [formatter](../format/struct.JSONFormatter.html) starts a new
+//! `data:` line, which browsers join back with a line feed,
+//! so a value can never end an event early.
+//! Strings never contain raw newlines, since they are always escaped.
+//!
+//! Every event is serialized to an intermediate buffer before being written,
+//! so a value that fails to serialize never leaves a truncated event in the output.
+//!
+//! This module is only available with the `alloc` feature.
+//!
+//! # Examples
+//!
+//! ```
+//! use json_in_type::*;
+//! use json_in_type::sse::{Event, SSEWriter};
+//!
+//! let mut sse = SSEWriter::new(vec![]);
+//! sse.write_event(&Event::new(json_object! { price: 42 }).event("quote").id("1")).unwrap();
+//! sse.write_data(&"done").unwrap();
+//! assert_eq!(
+//!     "event: quote\nid: 1\ndata: {\"price\":42}\n\ndata: \"done\"\n\n",
+//!     String::from_utf8(sse.into_inner()).unwrap()
+//! );
+//! ```
+
+use super::error::JSONError;
+use super::format::JSONFormatter;
+use super::write::{ByteBuffer, JSONWrite};
+use super::JSONValue;
+use alloc::vec::Vec;
+
+/// An event, whose data is a JSON value
+#[derive(Clone, Copy, Debug)]
+pub struct Event<'a, T: JSONValue> {
+    data: T,
+    event: Option<&'a str>,
+    id: Option<&'a str>,
+    retry: Option<u32>,
+}
+
+impl<'a, T: JSONValue> Event<'a, T> {
+    /// Creates an event without name nor id, that browsers dispatch as a `message` event
+    pub fn new(data: T) -> Event<'a, T> {
+        Event {
+            data,
+            event: None,
+            id: None,
+            retry: None,
+        }
+    }
+
+    /// Sets the name of the event.
+    /// It must not contain line breaks.
+    pub fn event(mut self, event: &'a str) -> Event<'a, T> {
+        self.event = Some(event);
+        self
+    }
+
+    /// Sets the id of the event, that the browser sends back when it reconnects.
+    /// It must not contain line breaks nor null characters.
+    pub fn id(mut self, id: &'a str) -> Event<'a, T> {
+        self.id = Some(id);
+        self
+    }
+
+    /// Sets the time in milliseconds that the browser waits before reconnecting
+    pub fn retry(mut self, retry: u32) -> Event<'a, T> {
+        self.retry = Some(retry);
+        self
+    }
+
+    /// Writes the event, with its data serialized by the given formatter, if any
+    fn write(&self, w: &mut ByteBuffer, f: Option<&mut JSONFormatter>) -> Result<(), JSONError> {
+        let breaks_line = |s: &str| s.contains(['\n', '\r']);
+        if self.event.is_some_and(breaks_line)
+            || self
+                .id
+                .is_some_and(|id| breaks_line(id) || id.contains('\0'))
+        {
+            return Err(JSONError::InvalidEventField);
+        }
+        if let Some(event) = self.event {
+            w.write_all(b"event: ")?;
+            w.write_all(event.as_bytes())?;
+            w.write_all(b"\n")?;
+        }
+        if let Some(id) = self.id {
+            w.write_all(b"id: ")?;
+            w.write_all(id.as_bytes())?;
+            w.write_all(b"\n")?;
+        }
+        if let Some(retry) = self.retry {
+            w.write_all(b"retry: ")?;
+            retry.write_json(w)?;
+            w.write_all(b"\n")?;
+        }
+        w.write_all(b"data: ")?;
+        let mut data = DataLines { w };
+        match f {
+            Some(f) => self.data.write_json_formatted(&mut data, f)?,
+            None => self.data.write_json(&mut data)?,
+        }
+        w.write_all(b"\n\n")
+    }
+}
+
+/// Starts a new `data:` line at every line feed, and drops carriage returns
+struct DataLines<'a, W: JSONWrite> {
+    w: &'a mut W,
+}
+
+impl<W: JSONWrite> JSONWrite for DataLines<'_, W> {
+    type Error = W::Error;
+
+    fn write_all(&mut self, buf: &[u8]) -> Result<(), W::Error> {
+        let mut rest = buf;
+        while let Some(pos) = rest.iter().position(|&b| b == b'\n' || b == b'\r') {
+            self.w.write_all(&rest[..pos])?;
+            if rest[pos] == b'\n' {
+                self.w.write_all(b"\ndata: ")?;
+            }
+            rest = &rest[pos + 1..];
+        }
+        self.w.write_all(rest)
+    }
+}
+
+/// Writes JSON values as server-sent events.
+pub struct SSEWriter<W: JSONWrite> {
+    writer: W,
+    formatter: Option<JSONFormatter>,
+    buffer: ByteBuffer,
+}
+
+impl<W: JSONWrite> SSEWriter<W> {
+    /// Creates a writer of events whose data is compact JSON
+    pub fn new(writer: W) -> SSEWriter<W> {
+        SSEWriter {
+            writer,
+            formatter: None,
+            buffer: ByteBuffer(Vec::new()),
+        }
+    }
+
+    /// Creates a writer of events whose data is serialized with the given formatter
+    pub fn with_formatter(writer: W, formatter: JSONFormatter) -> SSEWriter<W> {
+        SSEWriter {
+            formatter: Some(formatter),
+            ..SSEWriter::new(writer)
+        }
+    }
+
+    /// Writes an event.
+    /// Fails with [`JSONError::InvalidEventField`](../error/enum.JSONError.html#variant.InvalidEventField)
+    /// if its name or id contains a forbidden character.
+    /// Nothing is written if the event is invalid, or if its data fails to serialize.
+    ///
+    /// The event is not streamed to the writer as it is serialized:
+    /// it is first built in a buffer, which is kept between events,
+    /// and then written with a single call.
+    /// This costs a copy of every event, but never leaves half an event in the output.
+    pub fn write_event<T: JSONValue>(&mut self, event: &Event<T>) -> Result<(), W::Error> {
+        self.buffer.0.clear();
+        // A failed event can leave a formatter nested, so every event gets a fresh one
+        event.write(&mut self.buffer, self.formatter.clone().as_mut())?;
+        self.writer.write_all(&self.buffer.0)
+    }
+
+    /// Writes an unnamed event with the given data
+    pub fn write_data<T: JSONValue + ?Sized>(&mut self, data: &T) -> Result<(), W::Error> {
+        self.write_event(&Event::new(data))
+    }
+
+    /// Writes every value of an iterator as an event with the given name, if any,
+    /// as they are produced by the iterator
+    pub fn write_stream<T, I>(&mut self, event: Option<&str>, values: I) -> Result<(), W::Error>
+    where
+        T: JSONValue,
+        I: IntoIterator<Item = T>,
+    {
+        values.into_iter().try_for_each(|data| {
+            let event = Event {
+                event,
+                ..Event::new(data)
+            };
+            self.write_event(&event)
+        })
+    }
+
+    /// Returns the underlying writer
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+    use crate::format::NonFiniteFloats;
+    use crate::*;
+
+    type WriteResult = Result<(), <Vec<u8> as JSONWrite>::Error>;
+
+    fn written<F: FnOnce(&mut SSEWriter<Vec<u8>>) -> WriteResult>(
+        sse: &mut SSEWriter<Vec<u8>>,
+        write: F,
+    ) -> String {
+        write(sse).unwrap();
+        String::from_utf8(std::mem::take(&mut sse.writer)).unwrap()
+    }
+
+    #[test]
+    fn test_fields() {
+        let mut sse = SSEWriter::new(vec![]);
+        let event = Event::new(json_list!["a\nb\r"])
+            .event("e")
+            .id("")
+            .retry(3000);
+        assert_eq!(
+            "event: e\nid: \nretry: 3000\ndata: [\"a\\nb\\r\"]\n\n",
+            written(&mut sse, |sse| sse.write_event(&event))
+        );
+
+        for event in &[
+            Event::new(1).event("a\nb"),
+            Event::new(1).event("\r"),
+            Event::new(1).id("a\0"),
+        ] {
+            let err = sse.write_event(event).unwrap_err();
+            #[cfg(feature = "std")]
+            assert_eq!(std::io::ErrorKind::InvalidInput, err.kind());
+            #[cfg(not(feature = "std"))]
+            assert_eq!(JSONError::InvalidEventField, err);
+        }
+        assert!(sse.writer.is_empty());
+    }
+
+    #[test]
+    fn test_formatted() {
+        let formatter = JSONFormatter::pretty().newline("\r\n");
+        let mut sse = SSEWriter::with_formatter(vec![], formatter);
+        assert_eq!(
+            "data: {\ndata:   \"x\": [\ndata:     1\ndata:   ]\ndata: }\n\n",
+            written(&mut sse, |sse| sse.write_data(&json_object! { x: vec![1] }))
+        );
+    }
+
+    #[test]
+    fn test_failed_data() {
+        let strict = JSONFormatter::compact().non_finite_floats(NonFiniteFloats::Error);
+        let mut sse = SSEWriter::with_formatter(vec![], strict);
+        sse.write_data(&1).unwrap();
+        let err = sse
+            .write_event(&Event::new(json_list![2., f64::NAN]).event("e"))
+            .unwrap_err();
+        #[cfg(feature = "std")]
+        assert_eq!(std::io::ErrorKind::InvalidData, err.kind());
+        #[cfg(not(feature = "std"))]
+        assert_eq!(JSONError::NonFiniteFloat, err);
+        assert_eq!(
+            "data: 1\n\ndata: 3\n\n",
+            written(&mut sse, |sse| sse.write_data(&3))
+        );
+
+        // A failed event does not change the indentation of the following ones
+        let strict = JSONFormatter::pretty().non_finite_floats(NonFiniteFloats::Error);
+        let mut sse = SSEWriter::with_formatter(vec![], strict);
+        assert!(sse.write_data(&json_list![json_list![f64::NAN]]).is_err());
+        assert_eq!(
+            "data: [\ndata:   1\ndata: ]\n\n",
+            written(&mut sse, |sse| sse.write_data(&json_list![1]))
+        );
+    }
+
+    #[test]
+    fn test_stream() {
+        let mut sse = SSEWriter::new(vec![]);
+        assert_eq!(
+            "event: n\ndata: 1\n\nevent: n\ndata: 2\n\n",
+            written(&mut sse, |sse| sse.write_stream(Some("n"), 1..=2))
+        );
+        assert_eq!(
+            "data: true\n\n",
+            written(&mut sse, |sse| sse.write_stream(None, vec![true]))
+        );
+    }
+}
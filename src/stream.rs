@@ -0,0 +1,352 @@
+//! Imperative serialization, one token at a time.
+//!
+//! Some documents are easier to produce with a sequence of calls than as a single value,
+//! for instance when walking a tree or exporting the rows of a database.
+//! A [`JSONStreamWriter`](struct.JSONStreamWriter.html) writes such a document
+//! directly to its writer, keeps track of the nesting of objects and lists,
+//! and rejects the calls that would produce an invalid document.
+//! Any [`JSONValue`](../trait.JSONValue.html) can be written as a leaf of the document.
+//!
+//! This module is only available with the `alloc` feature.
+//!
+//! # Examples
+//!
+//! ```
+//! use json_in_type::*;
+//! use json_in_type::stream::JSONStreamWriter;
+//!
+//! let mut json = JSONStreamWriter::new(vec![]);
+//! json.begin_object().unwrap();
+//! json.key("rows").unwrap();
+//! json.begin_array().unwrap();
+//! for id in 1..3 {
+//!     json.value(&json_object! { id }).unwrap();
+//! }
+//! json.end().unwrap();
+//! json.end().unwrap();
+//! assert_eq!(br#"{"rows":[{"id":1},{"id":2}]}"#.to_vec(), json.finish().unwrap());
+//! ```
+
+use super::error::JSONError;
+use super::format::JSONFormatter;
+use super::write::JSONWrite;
+use super::JSONValue;
+use alloc::vec::Vec;
+
+/// An object or a list that has been begun, but not ended yet
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Container {
+    Object { empty: bool, has_key: bool },
+    Array { empty: bool },
+}
+
+/// Writes a JSON document imperatively, one token at a time.
+///
+/// The methods that would make the document invalid, such as writing a value
+/// where an object key is expected, or ending a list that was not begun,
+/// return [`JSONError::InvalidStructure`](../error/enum.JSONError.html#variant.InvalidStructure)
+/// without writing anything, so the writer can still be used after them.
+/// After any other error, such as an error of the underlying writer
+/// or a value that failed to serialize, the document is left incomplete,
+/// and every later call fails with
+/// [`JSONError::Poisoned`](../error/enum.JSONError.html#variant.Poisoned).
+pub struct JSONStreamWriter<W: JSONWrite> {
+    writer: W,
+    formatter: JSONFormatter,
+    /// Whether values are written with the formatter, or with the faster compact serialization
+    formatted: bool,
+    stack: Vec<Container>,
+    complete: bool,
+    /// Whether a write is in progress, or has failed and left a partial token in the document
+    poisoned: bool,
+}
+
+impl<W: JSONWrite> JSONStreamWriter<W> {
+    /// Creates a writer for a compact document
+    pub fn new(writer: W) -> JSONStreamWriter<W> {
+        JSONStreamWriter {
+            writer,
+            formatter: JSONFormatter::compact(),
+            formatted: false,
+            stack: Vec::new(),
+            complete: false,
+            poisoned: false,
+        }
+    }
+
+    /// Creates a writer whose tokens and values are written with the given formatter.
+    /// Since members are written as they come,
+    /// the [`sort_keys`](../format/struct.JSONFormatter.html#method.sort_keys) option
+    /// only applies inside of the values.
+    pub fn with_formatter(writer: W, formatter: JSONFormatter) -> JSONStreamWriter<W> {
+        JSONStreamWriter {
+            formatter,
+            formatted: true,
+            ..JSONStreamWriter::new(writer)
+        }
+    }
+
+    /// The number of objects and lists that have been begun and not ended yet
+    #[inline]
+    pub fn depth(&self) -> usize {
+        self.stack.len()
+    }
+
+    /// Whether a whole document has been written
+    #[inline]
+    pub fn is_complete(&self) -> bool {
+        self.complete
+    }
+
+    /// Fails if a previous write failed
+    fn check_poisoned(&self) -> Result<(), W::Error> {
+        if self.poisoned {
+            Err(JSONError::Poisoned.into())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Checks that a value can be written, and writes the separator that precedes it.
+    /// The writer is poisoned until the value has been written.
+    fn begin_value(&mut self) -> Result<(), W::Error> {
+        self.check_poisoned()?;
+        let f = &mut self.formatter;
+        match self.stack.last_mut() {
+            None if !self.complete => {}
+            Some(Container::Array { empty }) => {
+                self.poisoned = true;
+                f.begin_array_value(&mut self.writer, *empty)?;
+                *empty = false;
+            }
+            Some(Container::Object { has_key, .. }) if *has_key => *has_key = false,
+            _ => return Err(JSONError::InvalidStructure.into()),
+        }
+        self.poisoned = true;
+        Ok(())
+    }
+
+    fn end_value(&mut self) {
+        self.complete = self.stack.is_empty();
+        self.poisoned = false;
+    }
+
+    /// Begins an object, whose members are then written by calling
+    /// [`key`](#method.key) and a value-writing method alternately
+    pub fn begin_object(&mut self) -> Result<(), W::Error> {
+        self.begin_value()?;
+        self.stack.push(Container::Object {
+            empty: true,
+            has_key: false,
+        });
+        self.formatter.begin_object(&mut self.writer)?;
+        self.poisoned = false;
+        Ok(())
+    }
+
+    /// Begins a list
+    pub fn begin_array(&mut self) -> Result<(), W::Error> {
+        self.begin_value()?;
+        self.stack.push(Container::Array { empty: true });
+        self.formatter.begin_array(&mut self.writer)?;
+        self.poisoned = false;
+        Ok(())
+    }
+
+    /// Writes the key of the next member of the current object
+    pub fn key(&mut self, key: &str) -> Result<(), W::Error> {
+        self.check_poisoned()?;
+        let f = &mut self.formatter;
+        match self.stack.last_mut() {
+            Some(Container::Object { empty, has_key }) if !*has_key => {
+                self.poisoned = true;
+                f.begin_object_key(&mut self.writer, *empty)?;
+                *empty = false;
+                *has_key = true;
+                f.write_string(&mut self.writer, key)?;
+                f.begin_object_value(&mut self.writer)?;
+                self.poisoned = false;
+                Ok(())
+            }
+            _ => Err(JSONError::InvalidStructure.into()),
+        }
+    }
+
+    /// Writes a value, that can be the whole document, an element of the current list,
+    /// or the value of the last key of the current object
+    pub fn value<T: JSONValue + ?Sized>(&mut self, value: &T) -> Result<(), W::Error> {
+        self.begin_value()?;
+        if self.formatted {
+            value.write_json_formatted(&mut self.writer, &mut self.formatter)?;
+        } else {
+            value.write_json(&mut self.writer)?;
+        }
+        self.end_value();
+        Ok(())
+    }
+
+    /// Writes a member of the current object
+    pub fn member<T: JSONValue + ?Sized>(&mut self, key: &str, value: &T) -> Result<(), W::Error> {
+        self.key(key)?;
+        self.value(value)
+    }
+
+    /// Ends the current object or list
+    pub fn end(&mut self) -> Result<(), W::Error> {
+        self.check_poisoned()?;
+        let f = &mut self.formatter;
+        match self.stack.last() {
+            Some(&Container::Object {
+                empty,
+                has_key: false,
+            }) => {
+                self.poisoned = true;
+                f.end_object(&mut self.writer, empty)?
+            }
+            Some(&Container::Array { empty }) => {
+                self.poisoned = true;
+                f.end_array(&mut self.writer, empty)?
+            }
+            _ => return Err(JSONError::InvalidStructure.into()),
+        }
+        self.stack.pop();
+        self.end_value();
+        Ok(())
+    }
+
+    /// Returns the underlying writer, once the whole document has been written
+    pub fn finish(self) -> Result<W, W::Error> {
+        self.check_poisoned()?;
+        if self.complete {
+            Ok(self.writer)
+        } else {
+            Err(JSONError::InvalidStructure.into())
+        }
+    }
+
+    /// Returns the underlying writer, even if the document is incomplete
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+    use crate::*;
+
+    fn written(json: JSONStreamWriter<Vec<u8>>) -> String {
+        String::from_utf8(json.finish().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_documents() {
+        let mut json = JSONStreamWriter::new(vec![]);
+        json.value(&42).unwrap();
+        assert!(json.is_complete());
+        assert_eq!("42", written(json));
+
+        let mut json = JSONStreamWriter::new(vec![]);
+        json.begin_array().unwrap();
+        json.begin_object().unwrap();
+        json.end().unwrap();
+        json.begin_array().unwrap();
+        assert_eq!(2, json.depth());
+        json.end().unwrap();
+        json.value(&"x").unwrap();
+        json.begin_object().unwrap();
+        json.member("a", &json_object! { b: vec![1, 2] }).unwrap();
+        json.key("c\n").unwrap();
+        json.value(&()).unwrap();
+        json.end().unwrap();
+        assert!(!json.is_complete());
+        json.end().unwrap();
+        assert_eq!(r#"[{},[],"x",{"a":{"b":[1,2]},"c\n":null}]"#, written(json));
+    }
+
+    #[test]
+    fn test_formatted() {
+        let mut json = JSONStreamWriter::with_formatter(vec![], JSONFormatter::pretty());
+        json.begin_object().unwrap();
+        json.member("list", &json_list![1, json_object! { x: 2 }])
+            .unwrap();
+        json.key("empty").unwrap();
+        json.begin_array().unwrap();
+        json.end().unwrap();
+        json.end().unwrap();
+        let expected = json_object! {
+            list: json_list![1, json_object! { x: 2 }],
+            empty: json_list![],
+        };
+        assert_eq!(expected.to_json_string_pretty(), written(json));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_invalid_structure() {
+        fn is_invalid(result: std::io::Result<()>) -> bool {
+            let err = result.unwrap_err();
+            let inner = err.get_ref().and_then(|e| e.downcast_ref::<JSONError>());
+            inner == Some(&JSONError::InvalidStructure)
+        }
+
+        let mut json = JSONStreamWriter::new(vec![]);
+        assert!(is_invalid(json.end()));
+        assert!(is_invalid(json.key("a")));
+        json.begin_object().unwrap();
+        assert!(is_invalid(json.value(&1)));
+        assert!(is_invalid(json.begin_array()));
+        json.key("a").unwrap();
+        assert!(is_invalid(json.key("b")));
+        assert!(is_invalid(json.end()));
+        json.begin_array().unwrap();
+        assert!(is_invalid(json.key("b")));
+        json.end().unwrap();
+        json.end().unwrap();
+        assert!(is_invalid(json.value(&1)));
+        assert!(is_invalid(json.begin_object()));
+        assert_eq!(r#"{"a":[]}"#, written(json));
+
+        let mut json = JSONStreamWriter::new(vec![]);
+        json.begin_array().unwrap();
+        assert_eq!(
+            std::io::ErrorKind::InvalidInput,
+            json.finish().unwrap_err().kind()
+        );
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_poisoned() {
+        use crate::format::NonFiniteFloats;
+
+        fn kind_of(result: std::io::Result<()>) -> Option<JSONError> {
+            let err = result.unwrap_err();
+            err.get_ref()
+                .and_then(|e| e.downcast_ref::<JSONError>())
+                .copied()
+        }
+
+        let strict = JSONFormatter::compact().non_finite_floats(NonFiniteFloats::Error);
+        let mut json = JSONStreamWriter::with_formatter(vec![], strict);
+        json.begin_array().unwrap();
+        json.value(&1.).unwrap();
+        let failed = json.value(&json_list![2., f64::NAN]);
+        assert_eq!(Some(JSONError::NonFiniteFloat), kind_of(failed));
+        assert_eq!(Some(JSONError::Poisoned), kind_of(json.value(&3)));
+        assert_eq!(Some(JSONError::Poisoned), kind_of(json.end()));
+        assert_eq!(
+            Some(JSONError::Poisoned),
+            kind_of(json.finish().map(|_| ()))
+        );
+
+        let mut full = [0u8; 4];
+        let mut json = JSONStreamWriter::new(&mut full[..]);
+        json.begin_object().unwrap();
+        let err = json.key("long").unwrap_err();
+        assert_eq!(std::io::ErrorKind::WriteZero, err.kind());
+        assert_eq!(Some(JSONError::Poisoned), kind_of(json.key("a")));
+        assert_eq!(Some(JSONError::Poisoned), kind_of(json.end()));
+    }
+}
@@ -1,6 +1,9 @@
 //! Serialization to JSON strings like `"hello world \n"`
+use super::format::{JSONFormatter, StringEscaping};
+use super::write::JSONWrite;
 use super::JSONValue;
-use std::io;
+#[cfg(feature = "alloc")]
+use alloc::{string::String, vec::Vec};
 
 static ESCAPE_CHARS: [&[u8]; 0x20] = [
     b"\\u0000", b"\\u0001", b"\\u0002", b"\\u0003", b"\\u0004", b"\\u0005", b"\\u0006", b"\\u0007",
@@ -37,127 +40,506 @@ fn json_escaped_char(c: u8) -> Option<&'static [u8]> {
     }
 }
 
+/// Length of a string once escaped, without the surrounding quotes, computed at compile time
+const fn const_escaped_len(bytes: &[u8]) -> usize {
+    let mut len = 0;
+    let mut i = 0;
+    while i < bytes.len() {
+        len += match bytes[i] {
+            b'"' | b'\\' | 0x08 | 0x09 | 0x0A | 0x0C | 0x0D => 2,
+            0..=0x1F | 0x7F => 6,
+            _ => 1,
+        };
+        i += 1;
+    }
+    len
+}
+
+/// Length of a string once escaped and quoted, as written by
+/// [`quoted_key`](fn.quoted_key.html)
+#[doc(hidden)]
+pub const fn quoted_key_len(key: &str) -> usize {
+    const_escaped_len(key.as_bytes()) + 2
+}
+
+/// The bytes of a string in json, escaped and quoted at compile time.
+/// `N` must be [`quoted_key_len(key)`](fn.quoted_key_len.html).
+#[doc(hidden)]
+pub const fn quoted_key<const N: usize>(key: &str) -> [u8; N] {
+    const_quoted(key, b"", b"")
+}
+
+/// Length of the bytes that start an object member with the given key: `{"key":` or `,"key":`
+#[doc(hidden)]
+pub const fn member_prefix_len(key: &str) -> usize {
+    quoted_key_len(key) + 2
+}
+
+/// The bytes that start an object member with the given key, computed at compile time:
+/// `start` (either `{` or `,`), the escaped key in quotes, and a colon.
+/// `N` must be [`member_prefix_len(key)`](fn.member_prefix_len.html).
+#[doc(hidden)]
+pub const fn member_prefix<const N: usize>(key: &str, start: u8) -> [u8; N] {
+    const_quoted(key, &[start], b":")
+}
+
+/// The escaped and quoted key, between `before` and `after`
+const fn const_quoted<const N: usize>(key: &str, before: &[u8], after: &[u8]) -> [u8; N] {
+    let (out, len) = copy_bytes([0; N], 0, before);
+    let (out, len) = write_quoted(out, len, key);
+    copy_bytes(out, len, after).0
+}
+
+/// Copies `bytes` to `out` at position `len`, and returns the new length, at compile time
+pub(crate) const fn copy_bytes<const N: usize>(
+    mut out: [u8; N],
+    len: usize,
+    bytes: &[u8],
+) -> ([u8; N], usize) {
+    let mut i = 0;
+    while i < bytes.len() {
+        out[len + i] = bytes[i];
+        i += 1;
+    }
+    (out, len + bytes.len())
+}
+
+/// Writes `key`, escaped and quoted, to `out` at position `len`,
+/// and returns the new length, at compile time
+pub(crate) const fn write_quoted<const N: usize>(
+    mut out: [u8; N],
+    mut len: usize,
+    key: &str,
+) -> ([u8; N], usize) {
+    const HEX_DIGITS: &[u8; 16] = b"0123456789abcdef";
+    let bytes = key.as_bytes();
+    out[len] = b'"';
+    len += 1;
+    let mut i = 0;
+    while i < bytes.len() {
+        let c = bytes[i];
+        let short_escape = match c {
+            b'"' => b'"',
+            b'\\' => b'\\',
+            0x08 => b'b',
+            0x09 => b't',
+            0x0A => b'n',
+            0x0C => b'f',
+            0x0D => b'r',
+            _ => 0,
+        };
+        if short_escape != 0 {
+            out[len] = b'\\';
+            out[len + 1] = short_escape;
+            len += 2;
+        } else if c < 0x20 || c == 0x7F {
+            out[len] = b'\\';
+            out[len + 1] = b'u';
+            out[len + 2] = b'0';
+            out[len + 3] = b'0';
+            out[len + 4] = HEX_DIGITS[(c >> 4) as usize];
+            out[len + 5] = HEX_DIGITS[(c & 0xF) as usize];
+            len += 6;
+        } else {
+            out[len] = c;
+            len += 1;
+        }
+        i += 1;
+    }
+    out[len] = b'"';
+    (out, len + 1)
+}
+
+/// A 128-bit FNV-1a hash of a key, computed at compile time.
+/// It identifies the key in the type of a static object,
+/// to find its member with [`json_field_mut!`](../macro.json_field_mut.html).
+#[doc(hidden)]
+pub const fn key_hash(key: &str) -> u128 {
+    const OFFSET_BASIS: u128 = 0x6c62272e07bb014262b821756295c58d;
+    const PRIME: u128 = 0x0000000001000000000000000000013B;
+    let bytes = key.as_bytes();
+    let mut hash = OFFSET_BASIS;
+    let mut i = 0;
+    while i < bytes.len() {
+        hash ^= bytes[i] as u128;
+        hash = hash.wrapping_mul(PRIME);
+        i += 1;
+    }
+    hash
+}
+
+/// The name of an identifier, without the `r#` prefix of raw identifiers
+#[doc(hidden)]
+pub const fn unraw_identifier(ident: &str) -> &str {
+    match ident.as_bytes() {
+        [b'r', b'#', rest @ ..] => match core::str::from_utf8(rest) {
+            Ok(name) => name,
+            Err(_) => ident,
+        },
+        _ => ident,
+    }
+}
+
 /// Implemented by types that can be serialized to a json string.
 ///
 /// Implement this trait for your type if you want to be able to use it as a
 /// key in a json object.
 pub trait JSONString: JSONValue {}
 
+/// A json string whose contents are known at compile time,
+/// escaped once and for all to be used as an object key.
+///
+/// It is implemented by the zero-sized keys created by [`json_key!`](../macro.json_key.html),
+/// and used by [`JSONObject::with_static`](../object/trait.JSONObject.html#method.with_static).
+pub trait StaticJSONKey: JSONString {
+    /// The key, unescaped
+    const KEY: &'static str;
+    /// The escaped key, preceded by `{` and followed by `:`
+    const FIRST_PREFIX: &'static [u8];
+    /// The escaped key, preceded by `,` and followed by `:`
+    const NEXT_PREFIX: &'static [u8];
+}
+
+/// The escape sequence of a character, if it is an ASCII character that needs escaping
+#[inline(always)]
+fn json_escaped_ascii_char(c: char) -> Option<&'static [u8]> {
+    if c.is_ascii() {
+        json_escaped_char(c as u8)
+    } else {
+        None
+    }
+}
+
 impl JSONValue for char {
-    fn write_json<W: io::Write>(&self, w: &mut W) -> io::Result<()> {
+    fn write_json<W: JSONWrite>(&self, w: &mut W) -> Result<(), W::Error> {
         w.write_all(b"\"")?;
-        if let Some(escaped) = json_escaped_char(*self as u8) {
+        if let Some(escaped) = json_escaped_ascii_char(*self) {
             w.write_all(escaped)?;
         } else {
-            write!(w, "{}", self)?;
+            w.write_all(self.encode_utf8(&mut [0; 4]).as_bytes())?;
         }
         w.write_all(b"\"")
     }
+
+    fn write_json_formatted<W: JSONWrite>(
+        &self,
+        w: &mut W,
+        f: &mut JSONFormatter,
+    ) -> Result<(), W::Error> {
+        f.write_string(w, self.encode_utf8(&mut [0; 4]))
+    }
+
+    fn json_len(&self) -> Option<usize> {
+        let content_len = match json_escaped_ascii_char(*self) {
+            Some(escaped) => escaped.len(),
+            None => self.len_utf8(),
+        };
+        Some(2 + content_len)
+    }
 }
 
 impl JSONString for char {}
 
-impl<'a> JSONValue for &'a str {
-    fn write_json<W: io::Write>(&self, w: &mut W) -> io::Result<()> {
+impl JSONValue for &str {
+    fn write_json<W: JSONWrite>(&self, w: &mut W) -> Result<(), W::Error> {
         w.write_all(b"\"")?;
-        write_json_common(self, w)?;
+        write_escaped(self, StringEscaping::Minimal, w)?;
         w.write_all(b"\"")
     }
+
+    fn write_json_formatted<W: JSONWrite>(
+        &self,
+        w: &mut W,
+        f: &mut JSONFormatter,
+    ) -> Result<(), W::Error> {
+        f.write_string(w, self)
+    }
+
+    fn json_len(&self) -> Option<usize> {
+        Some(2 + escaped_len(self.as_bytes()))
+    }
 }
 
-fn write_json_common<W: io::Write>(s: &str, w: &mut W) -> io::Result<()> {
-    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
-        {
-            if is_x86_feature_detected!("sse4.2") {
-                return unsafe { write_json_simd(s, w) };
-            }
+/// Length of a string once escaped, without the surrounding quotes
+fn escaped_len(bytes: &[u8]) -> usize {
+    bytes
+        .iter()
+        .map(|&c| json_escaped_char(c).map_or(1, <[u8]>::len))
+        .sum()
+}
+
+/// Write the contents of a JSON string, without the surrounding quotes,
+/// escaping the characters that the given escaping mode requires
+pub(crate) fn write_escaped<W: JSONWrite>(
+    s: &str,
+    escaping: StringEscaping,
+    w: &mut W,
+) -> Result<(), W::Error> {
+    // Runtime CPU feature detection is only available in the standard library
+    #[cfg(all(feature = "std", any(target_arch = "x86", target_arch = "x86_64")))]
+    {
+        if is_x86_feature_detected!("sse4.2") {
+            return unsafe { write_escaped_simd(s, escaping, w) };
         }
-    write_json_nosimd_prevalidated(s.as_bytes(), 0, 0, w)
+    }
+    write_escaped_until(s, 0, 0, s.len(), escaping, w).map(|_| ())
 }
 
-#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[cfg(all(feature = "std", any(target_arch = "x86", target_arch = "x86_64")))]
 #[target_feature(enable = "sse4.2")]
 #[allow(clippy::cast_ptr_alignment)]
-unsafe fn write_json_simd<W: io::Write>(s: &str, w: &mut W) -> io::Result<()> {
-    use std::arch::x86_64::*;
-    use std::mem::size_of;
+unsafe fn write_escaped_simd<W: JSONWrite>(
+    s: &str,
+    escaping: StringEscaping,
+    w: &mut W,
+) -> Result<(), W::Error> {
+    #[cfg(target_arch = "x86")]
+    use core::arch::x86::*;
+    #[cfg(target_arch = "x86_64")]
+    use core::arch::x86_64::*;
+    use core::mem::size_of;
 
     const VECTOR_SIZE: usize = size_of::<__m128i>();
 
     let bytes = s.as_bytes();
-    let control_chars = _mm_setr_epi8(0, 0x1f, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0);
     let slash = b'\\' as i8;
     let quote = b'"' as i8;
-    let del = 0x7F as i8;
-    let special_chars = _mm_setr_epi8(slash, quote, del, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0);
+    let del = 0x7F_i8;
+    // Byte ranges that need escaping: control characters,
+    // and in ASCII mode, DEL and all non-ASCII bytes
+    let control_chars = _mm_setr_epi8(0, 0x1f, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0);
+    let (ranges, ranges_len) = match escaping {
+        StringEscaping::Minimal | StringEscaping::Html | StringEscaping::Canonical => {
+            (control_chars, 2)
+        }
+        StringEscaping::Ascii => (
+            _mm_setr_epi8(0, 0x1f, 0x7f, -1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0),
+            4,
+        ),
+    };
+    // Single bytes that need escaping. In HTML mode, 0xE2 is the first byte of U+2028 and U+2029.
+    let json_special_chars =
+        _mm_setr_epi8(slash, quote, del, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0);
+    let (special_chars, special_chars_len) = match escaping {
+        StringEscaping::Minimal | StringEscaping::Ascii => (json_special_chars, 3),
+        StringEscaping::Canonical => (json_special_chars, 2), // All but DEL
+        StringEscaping::Html => {
+            let (lt, gt, amp, solidus) = (b'<' as i8, b'>' as i8, b'&' as i8, b'/' as i8);
+            let line_separator_start = 0xE2_u8 as i8;
+            (
+                _mm_setr_epi8(
+                    slash,
+                    quote,
+                    del,
+                    lt,
+                    gt,
+                    amp,
+                    solidus,
+                    line_separator_start,
+                    0,
+                    0,
+                    0,
+                    0,
+                    0,
+                    0,
+                    0,
+                    0,
+                ),
+                8,
+            )
+        }
+    };
 
     let mut char_index_to_write = 0;
     let mut current_index = 0;
     for chunk_bytes in bytes.chunks_exact(VECTOR_SIZE) {
         let chunk = _mm_loadu_si128(chunk_bytes.as_ptr() as *const _);
-        let idx_control_chars = _mm_cmpestri(
-            control_chars,
-            2,
+        let idx_ranges = _mm_cmpestri(
+            ranges,
+            ranges_len,
             chunk,
             VECTOR_SIZE as i32,
             _SIDD_CMP_RANGES,
         );
         let idx_special_chars = _mm_cmpestri(
             special_chars,
-            3,
+            special_chars_len,
             chunk,
             VECTOR_SIZE as i32,
             _SIDD_CMP_EQUAL_ANY,
         );
-        let needs_write_at = idx_special_chars.min(idx_control_chars) as usize;
-        if needs_write_at != VECTOR_SIZE {
-            let chunk_end = current_index + VECTOR_SIZE;
-            write_json_nosimd_prevalidated(
-                &bytes[..chunk_end],
+        let needs_write_at = idx_special_chars.min(idx_ranges) as usize;
+        let chunk_end = current_index + VECTOR_SIZE;
+        // The previous write may have ended after the start of this chunk,
+        // in the middle of a multi-byte character
+        if needs_write_at != VECTOR_SIZE && chunk_end > char_index_to_write {
+            char_index_to_write = write_escaped_until(
+                s,
                 char_index_to_write,
-                current_index + needs_write_at,
+                char_index_to_write.max(current_index + needs_write_at),
+                chunk_end,
+                escaping,
                 w,
             )?;
-            char_index_to_write = chunk_end;
         }
-        current_index += VECTOR_SIZE;
+        current_index = chunk_end;
     }
-    write_json_nosimd_prevalidated(&bytes, char_index_to_write, current_index, w)
+    let current_index = char_index_to_write.max(current_index);
+    write_escaped_until(
+        s,
+        char_index_to_write,
+        current_index,
+        bytes.len(),
+        escaping,
+        w,
+    )
+    .map(|_| ())
 }
 
-fn write_json_nosimd_prevalidated<W: io::Write>(
-    bytes: &[u8],
+/// Write the bytes of `s` from `char_index_to_write` to at least `end`,
+/// knowing that no byte before `current_index` needs escaping.
+///
+/// Returns the index of the first byte that was not written.
+/// It is greater than `end` when `end` is in the middle of a multi-byte character,
+/// so that characters are never split between two writes.
+fn write_escaped_until<W: JSONWrite>(
+    s: &str,
     char_index_to_write: usize,
     current_index: usize,
+    end: usize,
+    escaping: StringEscaping,
     w: &mut W,
-) -> io::Result<()> {
+) -> Result<usize, W::Error> {
+    let bytes = s.as_bytes();
     let mut char_index_to_write = char_index_to_write;
     let mut current_index = current_index;
-    for &c in bytes[current_index..].iter() {
-        if let Some(escaped) = json_escaped_char(c) {
+    while current_index < end {
+        let c = bytes[current_index];
+        let escaped = match json_escaped_char(c) {
+            None if escaping == StringEscaping::Html => {
+                html_escaped_sequence(&bytes[current_index..])
+            }
+            Some(_) if c == 0x7F && escaping == StringEscaping::Canonical => None,
+            escaped => escaped.map(|escaped| (escaped, 1)),
+        };
+        if let Some((escaped, escaped_len)) = escaped {
             w.write_all(&bytes[char_index_to_write..current_index])?;
             w.write_all(escaped)?;
-            char_index_to_write = current_index + 1;
+            char_index_to_write = current_index + escaped_len;
+            current_index = char_index_to_write;
+            continue;
+        } else if c >= 0x80 && escaping == StringEscaping::Ascii {
+            let non_ascii = s[current_index..].chars().next().unwrap();
+            w.write_all(&bytes[char_index_to_write..current_index])?;
+            write_unicode_escape(non_ascii, w)?;
+            char_index_to_write = current_index + non_ascii.len_utf8();
+            current_index = char_index_to_write;
+            continue;
         }
         current_index += 1;
     }
-    w.write_all(&bytes[char_index_to_write..])
+    while !s.is_char_boundary(current_index) {
+        current_index += 1;
+    }
+    w.write_all(&bytes[char_index_to_write..current_index])?;
+    Ok(current_index)
 }
 
-impl<'a> JSONString for &'a str {}
+/// The escape sequence of the character at the start of `bytes`,
+/// if it has to be escaped in a string embedded in HTML,
+/// and the number of bytes it replaces
+#[inline]
+fn html_escaped_sequence(bytes: &[u8]) -> Option<(&'static [u8], usize)> {
+    match bytes {
+        [b'<', ..] => Some((&b"\\u003c"[..], 1)),
+        [b'>', ..] => Some((&b"\\u003e"[..], 1)),
+        [b'&', ..] => Some((&b"\\u0026"[..], 1)),
+        [b'/', ..] => Some((&b"\\/"[..], 1)),
+        // U+2028 LINE SEPARATOR and U+2029 PARAGRAPH SEPARATOR are not allowed in JavaScript strings before ES2019
+        [0xE2, 0x80, 0xA8, ..] => Some((&b"\\u2028"[..], 3)),
+        [0xE2, 0x80, 0xA9, ..] => Some((&b"\\u2029"[..], 3)),
+        _ => None,
+    }
+}
+
+/// The UTF-16 code units of the string represented by a JSON string literal, such as `"a\n"`.
+/// Only the escape sequences written by this crate are supported.
+#[cfg(feature = "alloc")]
+pub(crate) fn json_string_utf16(json: &[u8]) -> Vec<u16> {
+    let content = json
+        .strip_prefix(b"\"")
+        .and_then(|json| json.strip_suffix(b"\""))
+        .and_then(|content| core::str::from_utf8(content).ok())
+        .unwrap_or_default();
+    let mut units = Vec::with_capacity(content.len());
+    let mut chars = content.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            units.extend_from_slice(c.encode_utf16(&mut [0; 2]));
+            continue;
+        }
+        units.push(match chars.next() {
+            Some('b') => 0x08,
+            Some('t') => 0x09,
+            Some('n') => 0x0A,
+            Some('f') => 0x0C,
+            Some('r') => 0x0D,
+            Some('u') => {
+                let hex: String = chars.by_ref().take(4).collect();
+                u16::from_str_radix(&hex, 16).unwrap_or_default()
+            }
+            Some(escaped) => escaped as u16, // \" \\ and \/
+            None => break,
+        });
+    }
+    units
+}
+
+/// Write a character as one `\uXXXX` escape sequence,
+/// or as two for characters outside of the basic multilingual plane (UTF-16 surrogate pairs)
+fn write_unicode_escape<W: JSONWrite>(c: char, w: &mut W) -> Result<(), W::Error> {
+    const HEX_DIGITS: &[u8; 16] = b"0123456789abcdef";
+    let mut escaped = [0; 12];
+    let mut len = 0;
+    for unit in c.encode_utf16(&mut [0; 2]) {
+        escaped[len..len + 2].copy_from_slice(b"\\u");
+        for (i, digit) in escaped[len + 2..len + 6].iter_mut().enumerate() {
+            *digit = HEX_DIGITS[(*unit >> (12 - 4 * i) & 0xF) as usize];
+        }
+        len += 6;
+    }
+    w.write_all(&escaped[..len])
+}
 
+impl JSONString for &str {}
+
+#[cfg(feature = "alloc")]
 impl JSONValue for String {
-    fn write_json<W: io::Write>(&self, w: &mut W) -> io::Result<()> {
+    fn write_json<W: JSONWrite>(&self, w: &mut W) -> Result<(), W::Error> {
         (self as &str).write_json(w)
     }
+
+    fn write_json_formatted<W: JSONWrite>(
+        &self,
+        w: &mut W,
+        f: &mut JSONFormatter,
+    ) -> Result<(), W::Error> {
+        (self as &str).write_json_formatted(w, f)
+    }
+
+    fn json_len(&self) -> Option<usize> {
+        (self as &str).json_len()
+    }
 }
 
+#[cfg(feature = "alloc")]
 impl JSONString for String {}
 
 #[cfg(test)]
 mod tests {
     // Note this useful idiom: importing names from outer (for mod tests) scope.
     use super::*;
+    use crate::JSON;
 
     #[test]
     fn test_chars() {
@@ -166,6 +548,7 @@ mod tests {
         assert_eq!("\"\\\\\"", '\\'.to_json_string());
         assert_eq!("\"\\u0000\"", '\0'.to_json_string());
         assert_eq!("\"❤\"", '❤'.to_json_string());
+        assert_eq!("\"\u{fe0f}\"", '\u{fe0f}'.to_json_string());
     }
 
     #[test]
@@ -232,10 +615,131 @@ mod tests {
         assert_eq!(format!("\"{}\"", target), source.to_json_string());
     }
 
+    #[test]
+    fn test_len() {
+        for s in &["", "x", "\"\\\n", "\0\x1F\x7F", "pépé ❤️ \u{2a6a5}"] {
+            assert_eq!(Some(s.to_json_string().len()), s.json_len());
+            for c in s.chars() {
+                assert_eq!(Some(c.to_json_string().len()), c.json_len());
+            }
+        }
+    }
+
+    #[test]
+    fn test_member_prefix() {
+        const KEY: &str = "a\"\\\n\t\x7f\x01é";
+        const LEN: usize = member_prefix_len(KEY);
+        const PREFIX: [u8; LEN] = member_prefix(KEY, b',');
+        assert_eq!(
+            format!(",{}:", KEY.to_json_string()).as_bytes(),
+            &PREFIX[..]
+        );
+        for c in (0..=0x7F).map(char::from) {
+            let key = c.to_string();
+            let mut prefix = [0; 10];
+            let len = member_prefix_len(&key);
+            prefix[..len].copy_from_slice(&member_prefix::<10>(&key, b'{')[..len]);
+            assert_eq!(
+                format!("{{{}:", c.to_json_string()).as_bytes(),
+                &prefix[..len]
+            );
+        }
+    }
+
+    #[test]
+    fn test_quoted_key() {
+        const KEY: &str = "a\"\\\n\x7fé";
+        const QUOTED: [u8; quoted_key_len(KEY)] = quoted_key(KEY);
+        assert_eq!(KEY.to_json_string().as_bytes(), &QUOTED[..]);
+        assert_eq!("type", unraw_identifier("r#type"));
+        assert_eq!("radius", unraw_identifier("radius"));
+    }
+
+    #[test]
+    fn test_key_hash() {
+        assert_eq!(0x6c62272e07bb014262b821756295c58d, key_hash(""));
+        assert_eq!(0xd228cb696f1a8caf78912b704e4a8964, key_hash("a"));
+        assert_ne!(key_hash("ab"), key_hash("ba"));
+    }
+
     #[test]
     fn many_backslashes() {
         let n = 7919;
         let s = String::from("\\").repeat(n);
         assert_eq!(format!("\"{}\"", s.repeat(2)), s.to_json_string());
     }
+
+    fn ascii_escaped<T: JSONValue>(value: T) -> String {
+        let mut formatter = JSONFormatter::compact().string_escaping(StringEscaping::Ascii);
+        let mut buffer = vec![];
+        value
+            .write_json_formatted(&mut buffer, &mut formatter)
+            .unwrap();
+        String::from_utf8(buffer).unwrap()
+    }
+
+    #[test]
+    fn test_ascii_escaping() {
+        assert_eq!(r#""hello""#, ascii_escaped("hello"));
+        assert_eq!(r#""p\u00e9p\u00e9\n""#, ascii_escaped("pépé\n"));
+        assert_eq!(r#""\u2764\ufe0f""#, ascii_escaped("❤️"));
+        assert_eq!(r#""\ud869\udea5""#, ascii_escaped('\u{2a6a5}'));
+        assert_eq!(r#""\ud83d\ude00 \"\u007f""#, ascii_escaped("😀 \"\x7F"));
+    }
+
+    #[test]
+    fn test_ascii_escaping_long_strings() {
+        for i in 0..40 {
+            let (xs, ys) = ("x".repeat(i), "y".repeat(i));
+            let s = format!("{}é\\{}\u{2a6a5}", xs, ys);
+            let expected = format!(r#"{}\u00e9\\{}\ud869\udea5"#, xs, ys);
+            assert_eq!(
+                format!("\"{}\"", expected.repeat(3)),
+                ascii_escaped(s.repeat(3))
+            );
+        }
+    }
+
+    #[test]
+    fn test_multibyte_chars_not_split() {
+        // Writing to a fmt::Write fails if a character is split between two writes
+        for i in 0..40 {
+            let s = format!("{}\n{}é", "x".repeat(i), "y".repeat(i));
+            assert_eq!(s.to_json_string(), JSON(s.as_str()).to_string());
+        }
+    }
+
+    fn html_escaped<T: JSONValue>(value: T) -> String {
+        let mut formatter = JSONFormatter::compact().string_escaping(StringEscaping::Html);
+        let mut buffer = vec![];
+        value
+            .write_json_formatted(&mut buffer, &mut formatter)
+            .unwrap();
+        String::from_utf8(buffer).unwrap()
+    }
+
+    #[test]
+    fn test_html_escaping() {
+        assert_eq!(
+            r#""\u003c\/script\u003e\u003cscript\u003e""#,
+            html_escaped("</script><script>")
+        );
+        assert_eq!(r#""a \u0026amp; b\/c""#, html_escaped("a &amp; b/c"));
+        assert_eq!(r#""\u2028\u2029❤️""#, html_escaped("\u{2028}\u{2029}❤️"));
+        assert_eq!(r#""\u003c""#, html_escaped('<'));
+        assert_eq!(r#""\u2028""#, html_escaped('\u{2028}'));
+    }
+
+    #[test]
+    fn test_html_escaping_long_strings() {
+        for i in 0..40 {
+            let (xs, ys) = ("x".repeat(i), "y".repeat(i));
+            let s = format!("{}</{}\u{2028}é❤", xs, ys);
+            let expected = format!(r#"{}\u003c\/{}\u2028é❤"#, xs, ys);
+            assert_eq!(
+                format!("\"{}\"", expected.repeat(3)),
+                html_escaped(s.repeat(3))
+            );
+        }
+    }
 }
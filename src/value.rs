@@ -0,0 +1,361 @@
+//! A dynamic JSON value, for data whose shape is only known at runtime.
+//! This requires the `alloc` feature.
+
+use super::format::JSONFormatter;
+use super::object::{json_len_object, write_object, write_object_formatted};
+use super::write::JSONWrite;
+use super::JSONValue;
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::iter::FromIterator;
+
+#[doc(hidden)]
+pub use alloc::vec;
+
+/// A JSON number, that keeps the type it was created from:
+/// integers are written without a fractional part, and floats as with `f64`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Number {
+    Int(i64),
+    UInt(u64),
+    Float(f64),
+}
+
+impl JSONValue for Number {
+    #[inline]
+    fn write_json<W: JSONWrite>(&self, w: &mut W) -> Result<(), W::Error> {
+        match self {
+            Number::Int(n) => n.write_json(w),
+            Number::UInt(n) => n.write_json(w),
+            Number::Float(n) => n.write_json(w),
+        }
+    }
+
+    fn write_json_formatted<W: JSONWrite>(
+        &self,
+        w: &mut W,
+        f: &mut JSONFormatter,
+    ) -> Result<(), W::Error> {
+        match self {
+            Number::Int(n) => n.write_json_formatted(w, f),
+            Number::UInt(n) => n.write_json_formatted(w, f),
+            Number::Float(n) => n.write_json_formatted(w, f),
+        }
+    }
+
+    fn json_len(&self) -> Option<usize> {
+        match self {
+            Number::Int(n) => n.json_len(),
+            Number::UInt(n) => n.json_len(),
+            Number::Float(n) => n.json_len(),
+        }
+    }
+}
+
+/// Any JSON value, built at runtime.
+///
+/// Prefer [`json_object!`](../macro.json_object.html) and [`json!`](../macro.json.html)
+/// when the shape of the value is known at compile time: they are much faster.
+/// A `Value` is serialized with the same string escaping and number formatting.
+///
+/// Objects keep their members in order, and their keys are not deduplicated.
+///
+/// # Examples
+///
+/// ```
+/// use json_in_type::*;
+/// use json_in_type::value::Value;
+///
+/// let mut plugins = vec![];
+/// for (name, version) in [("a", 1), ("b", 2)] {
+///     plugins.push(value!({ "name": name, "version": version }));
+/// }
+/// let output = value!({ "plugins": plugins, "errors": [], "ok": true });
+///
+/// assert_eq!(
+///     r#"{"plugins":[{"name":"a","version":1},{"name":"b","version":2}],"errors":[],"ok":true}"#,
+///     output.to_json_string()
+/// );
+/// assert_eq!(Value::Null, Value::from(None::<u8>));
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum Value {
+    #[default]
+    Null,
+    Bool(bool),
+    Number(Number),
+    String(String),
+    Array(Vec<Value>),
+    Object(Vec<(String, Value)>),
+}
+
+impl JSONValue for Value {
+    fn write_json<W: JSONWrite>(&self, w: &mut W) -> Result<(), W::Error> {
+        match self {
+            Value::Null => ().write_json(w),
+            Value::Bool(b) => b.write_json(w),
+            Value::Number(n) => n.write_json(w),
+            Value::String(s) => s.write_json(w),
+            Value::Array(values) => values.write_json(w),
+            Value::Object(members) => write_object(w, &mut members.iter().map(|(k, v)| (k, v))),
+        }
+    }
+
+    fn write_json_formatted<W: JSONWrite>(
+        &self,
+        w: &mut W,
+        f: &mut JSONFormatter,
+    ) -> Result<(), W::Error> {
+        match self {
+            Value::Null => ().write_json_formatted(w, f),
+            Value::Bool(b) => b.write_json_formatted(w, f),
+            Value::Number(n) => n.write_json_formatted(w, f),
+            Value::String(s) => s.write_json_formatted(w, f),
+            Value::Array(values) => values.write_json_formatted(w, f),
+            Value::Object(members) => {
+                write_object_formatted(w, f, &mut members.iter().map(|(k, v)| (k, v)))
+            }
+        }
+    }
+
+    fn json_len(&self) -> Option<usize> {
+        match self {
+            Value::Null => ().json_len(),
+            Value::Bool(b) => b.json_len(),
+            Value::Number(n) => n.json_len(),
+            Value::String(s) => s.json_len(),
+            Value::Array(values) => values.json_len(),
+            Value::Object(members) => json_len_object(members.iter().map(|(k, v)| (k, v))),
+        }
+    }
+}
+
+macro_rules! impl_from_number {
+    ( $variant:ident ( $wide:ty ) : $( $number:ty ),* ) => {
+        $(
+            impl From<$number> for Number {
+                fn from(n: $number) -> Self {
+                    Number::$variant(n as $wide)
+                }
+            }
+
+            impl From<$number> for Value {
+                fn from(n: $number) -> Self {
+                    Value::Number(Number::from(n))
+                }
+            }
+        )*
+    };
+}
+
+impl_from_number!(Int(i64): i8, i16, i32, i64, isize);
+impl_from_number!(UInt(u64): u8, u16, u32, u64, usize);
+impl_from_number!(Float(f64): f32, f64);
+
+impl From<Number> for Value {
+    fn from(n: Number) -> Self {
+        Value::Number(n)
+    }
+}
+
+impl From<()> for Value {
+    fn from(_: ()) -> Self {
+        Value::Null
+    }
+}
+
+impl From<bool> for Value {
+    fn from(b: bool) -> Self {
+        Value::Bool(b)
+    }
+}
+
+impl From<char> for Value {
+    fn from(c: char) -> Self {
+        Value::String(c.into())
+    }
+}
+
+impl From<&str> for Value {
+    fn from(s: &str) -> Self {
+        Value::String(s.into())
+    }
+}
+
+impl From<&String> for Value {
+    fn from(s: &String) -> Self {
+        Value::String(s.clone())
+    }
+}
+
+impl From<String> for Value {
+    fn from(s: String) -> Self {
+        Value::String(s)
+    }
+}
+
+/// `None` is converted to `null`
+impl<T: Into<Value>> From<Option<T>> for Value {
+    fn from(option: Option<T>) -> Self {
+        option.map_or(Value::Null, Into::into)
+    }
+}
+
+impl<T: Into<Value>> From<Vec<T>> for Value {
+    fn from(values: Vec<T>) -> Self {
+        Value::Array(values.into_iter().map(Into::into).collect())
+    }
+}
+
+/// The members are kept in the order of their keys
+impl<K: Into<String>, V: Into<Value>> From<BTreeMap<K, V>> for Value {
+    fn from(map: BTreeMap<K, V>) -> Self {
+        Value::Object(map.into_iter().map(|(k, v)| (k.into(), v.into())).collect())
+    }
+}
+
+/// Collects values into an array
+impl<T: Into<Value>> FromIterator<T> for Value {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        Value::Array(iter.into_iter().map(Into::into).collect())
+    }
+}
+
+/// A member of an object created by [`value!`](../macro.value.html)
+#[doc(hidden)]
+pub fn member<K: Into<String>>(key: K, value: Value) -> (String, Value) {
+    (key.into(), value)
+}
+
+/// Creates a dynamic [`Value`](value/enum.Value.html) from JSON-like syntax,
+/// the same as the one of [`json!`](macro.json.html).
+///
+/// Object keys are string literals, or Rust expressions in square brackets
+/// that can be converted to a `String`.
+/// Values are `null`, nested objects and lists,
+/// or any Rust expression that can be converted to a `Value`.
+/// This requires the `alloc` feature.
+///
+/// # Examples
+///
+/// ```
+/// use json_in_type::*;
+///
+/// let key = String::from("dynamic");
+/// let values = vec![1.5, 2.];
+/// let config = value!({
+///     "name": "plugin",
+///     "values": values,
+///     "nested": [null, {"enabled": true}, []],
+///     [key]: Some("x"),
+/// });
+///
+/// assert_eq!(
+///     r#"{"name":"plugin","values":[1.5,2],"nested":[null,{"enabled":true},[]],"dynamic":"x"}"#,
+///     config.to_json_string()
+/// );
+/// ```
+#[macro_export]
+macro_rules! value {
+    (null) => { $crate::value::Value::Null };
+    ([ $($list:tt)* ]) => { $crate::value::Value::Array(value!(@list [] $($list)*)) };
+    ({ $($object:tt)* }) => { $crate::value::Value::Object(value!(@object [] $($object)*)) };
+    ($value:expr) => { $crate::value::Value::from($value) };
+
+    // The elements of a list, one at a time
+    (@list [$($elems:expr,)*]) => { $crate::value::vec![$($elems),*] };
+    (@list [$($elems:expr,)*] null $(, $($rest:tt)*)?) => {
+        value!(@list [$($elems,)* value!(null),] $($($rest)*)?)
+    };
+    (@list [$($elems:expr,)*] [ $($list:tt)* ] $(, $($rest:tt)*)?) => {
+        value!(@list [$($elems,)* value!([ $($list)* ]),] $($($rest)*)?)
+    };
+    (@list [$($elems:expr,)*] { $($object:tt)* } $(, $($rest:tt)*)?) => {
+        value!(@list [$($elems,)* value!({ $($object)* }),] $($($rest)*)?)
+    };
+    (@list [$($elems:expr,)*] $elem:expr $(, $($rest:tt)*)?) => {
+        value!(@list [$($elems,)* value!($elem),] $($($rest)*)?)
+    };
+
+    // The members of an object, one at a time
+    (@object [$($members:expr,)*]) => { $crate::value::vec![$($members),*] };
+    (@object [$($members:expr,)*] $key:tt : null $(, $($rest:tt)*)?) => {
+        value!(@member [$($members,)*] $key, value!(null), $($($rest)*)?)
+    };
+    (@object [$($members:expr,)*] $key:tt : [ $($list:tt)* ] $(, $($rest:tt)*)?) => {
+        value!(@member [$($members,)*] $key, value!([ $($list)* ]), $($($rest)*)?)
+    };
+    (@object [$($members:expr,)*] $key:tt : { $($object:tt)* } $(, $($rest:tt)*)?) => {
+        value!(@member [$($members,)*] $key, value!({ $($object)* }), $($($rest)*)?)
+    };
+    (@object [$($members:expr,)*] $key:tt : $value:expr $(, $($rest:tt)*)?) => {
+        value!(@member [$($members,)*] $key, value!($value), $($($rest)*)?)
+    };
+    (@member [$($members:expr,)*] [ $key:expr ], $value:expr, $($rest:tt)*) => {
+        value!(@object [$($members,)* $crate::value::member($key, $value),] $($rest)*)
+    };
+    (@member [$($members:expr,)*] $key:literal, $value:expr, $($rest:tt)*) => {
+        value!(@object [$($members,)* $crate::value::member($key, $value),] $($rest)*)
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+
+    #[test]
+    fn test_conversions() {
+        assert_eq!(Value::Null, Value::from(()));
+        assert_eq!(Value::Bool(true), Value::from(true));
+        assert_eq!(Value::Number(Number::Int(-1)), Value::from(-1i8));
+        assert_eq!(Value::Number(Number::UInt(1)), Value::from(1usize));
+        assert_eq!(Value::Number(Number::Float(0.5)), Value::from(0.5f32));
+        assert_eq!(Value::String("x".into()), Value::from('x'));
+        assert_eq!(Value::Null, Value::from(None::<&str>));
+        assert_eq!(
+            Value::Array(vec![Value::from(1)]),
+            Value::from(vec![Some(1)])
+        );
+        assert_eq!(Value::from(vec![1, 2]), (1..=2).collect());
+        let map: BTreeMap<_, _> = vec![("b", 2), ("a", 1)].into_iter().collect();
+        assert_eq!(value!({"a": 1, "b": 2}), Value::from(map));
+    }
+
+    #[test]
+    fn test_serialization() {
+        fn check(expected: &str, value: Value) {
+            assert_eq!(expected, value.to_json_string());
+            assert_eq!(Some(expected.len()), value.json_len());
+        }
+        check("null", value!(null));
+        check("[]", value!([]));
+        check("{}", value!({}));
+        check(
+            "[1,-2,3.5,null,true]",
+            value!([1u8, -2, 3.5, f64::NAN, true]),
+        );
+        check(
+            r#"{"a\"b":"c\nd","e":[{}],"f":{"g":null}}"#,
+            value!({"a\"b": "c\nd", "e": [{}], "f": {"g": null},}),
+        );
+        check("18446744073709551615", value!(u64::MAX));
+        let key = "k";
+        check(r#"{"k":[1,2]}"#, value!({ [key]: [1, 1 + 1] }));
+    }
+
+    #[test]
+    fn test_formatted() {
+        let value = value!({"b": [1, {}], "a": -0.0});
+        assert_eq!(
+            "{\n  \"b\": [\n    1,\n    {}\n  ],\n  \"a\": -0\n}",
+            value.to_json_string_pretty()
+        );
+        let mut canonical = Vec::new();
+        value
+            .write_json_formatted(&mut canonical, &mut JSONFormatter::canonical())
+            .unwrap();
+        assert_eq!(br#"{"a":0,"b":[1,{}]}"#.to_vec(), canonical);
+    }
+}
@@ -0,0 +1,114 @@
+//! Destinations to which JSON can be written
+//!
+//! All the serialization functions of this crate write to a [`JSONWrite`](trait.JSONWrite.html).
+//! With the `std` feature (enabled by default), every
+//! [`std::io::Write`](https://doc.rust-lang.org/std/io/trait.Write.html)
+//! is a `JSONWrite`, so files, sockets and `Vec<u8>` can be used directly.
+//! Without it, the crate can be used in `#![no_std]` environments, by implementing
+//! `JSONWrite` for the destination, or by wrapping a
+//! [`core::fmt::Write`](https://doc.rust-lang.org/core/fmt/trait.Write.html)
+//! in a [`FmtWriter`](struct.FmtWriter.html).
+//!
+//! # Examples
+//!
+//! Write JSON to a serial port on an embedded device
+//!
+//! ```
+//! use json_in_type::*;
+//! use json_in_type::error::JSONError;
+//! use json_in_type::write::JSONWrite;
+//!
+//! struct Uart { sent: usize }
+//!
+//! impl Uart {
+//!     fn send_byte(&mut self, _byte: u8) { self.sent += 1 }
+//! }
+//!
+//! impl JSONWrite for Uart {
+//!     type Error = JSONError;
+//!     fn write_all(&mut self, buf: &[u8]) -> Result<(), JSONError> {
+//!         buf.iter().for_each(|&b| self.send_byte(b));
+//!         Ok(())
+//!     }
+//! }
+//!
+//! let mut uart = Uart { sent: 0 };
+//! json_object! { temperature: 21.5 }.write_json(&mut uart).unwrap();
+//! assert_eq!(20, uart.sent);
+//! ```
+
+use super::error::JSONError;
+use core::fmt;
+use core::str;
+
+/// A destination for the bytes of serialized JSON
+pub trait JSONWrite {
+    /// The error returned when the bytes cannot be written,
+    /// or when the value cannot be serialized
+    type Error: From<JSONError>;
+
+    /// Write all the given bytes, or return an error
+    fn write_all(&mut self, buf: &[u8]) -> Result<(), Self::Error>;
+}
+
+#[cfg(feature = "std")]
+impl<W: std::io::Write> JSONWrite for W {
+    type Error = std::io::Error;
+
+    #[inline(always)]
+    fn write_all(&mut self, buf: &[u8]) -> Result<(), Self::Error> {
+        std::io::Write::write_all(self, buf)
+    }
+}
+
+#[cfg(all(feature = "alloc", not(feature = "std")))]
+impl JSONWrite for alloc::vec::Vec<u8> {
+    type Error = JSONError;
+
+    #[inline(always)]
+    fn write_all(&mut self, buf: &[u8]) -> Result<(), Self::Error> {
+        self.extend_from_slice(buf);
+        Ok(())
+    }
+}
+
+/// Converts a [`core::fmt::Write`](https://doc.rust-lang.org/core/fmt/trait.Write.html),
+/// such as a `String` or a `Formatter`, to a [`JSONWrite`](trait.JSONWrite.html).
+///
+/// # Examples
+///
+/// ```
+/// use json_in_type::*;
+/// use json_in_type::write::FmtWriter;
+///
+/// let mut s = String::new();
+/// json_list![1, 2].write_json(&mut FmtWriter(&mut s)).unwrap();
+/// assert_eq!("[1,2]", s);
+/// ```
+pub struct FmtWriter<W: fmt::Write>(pub W);
+
+impl<W: fmt::Write> JSONWrite for FmtWriter<W> {
+    type Error = fmt::Error;
+
+    fn write_all(&mut self, buf: &[u8]) -> Result<(), Self::Error> {
+        // Values never split a multi-byte character between two calls
+        let buf_str = str::from_utf8(buf).map_err(|_err| fmt::Error)?;
+        self.0.write_str(buf_str)
+    }
+}
+
+/// An in-memory buffer, used to serialize parts of a document in advance.
+/// Unlike `Vec<u8>`, its error type does not depend on the `std` feature.
+#[cfg(feature = "alloc")]
+pub(crate) struct ByteBuffer(pub alloc::vec::Vec<u8>);
+
+#[cfg(feature = "alloc")]
+impl JSONWrite for ByteBuffer {
+    type Error = JSONError;
+
+    #[inline(always)]
+    fn write_all(&mut self, buf: &[u8]) -> Result<(), Self::Error> {
+        self.0.extend_from_slice(buf);
+        Ok(())
+    }
+}
@@ -62,3 +62,317 @@ fn handwritten_equivalent() {
     }
     assert_eq!(write_obj_bad(42), write_obj_good(42));
 }
+
+#[test]
+fn test_optional_keys() {
+    fn check(a: Option<u8>, b: Option<&str>, expected: &str) {
+        let obj = json_object! { a?: a, b?: b };
+        assert_eq!(expected, obj.to_json_string());
+        assert_eq!(Some(expected.len()), obj.json_len());
+    }
+    check(None, None, "{}");
+    check(Some(1), None, r#"{"a":1}"#);
+    check(None, Some("x"), r#"{"b":"x"}"#);
+    check(Some(1), Some("x"), r#"{"a":1,"b":"x"}"#);
+
+    let missing: Option<u8> = None;
+    let obj = json_object! { missing?, x: 1, [String::from("dyn")]?: missing, y?: Some(2) };
+    assert_eq!(r#"{"x":1,"y":2}"#, obj.to_json_string());
+    assert_eq!("{\n  \"x\": 1,\n  \"y\": 2\n}", obj.to_json_string_pretty());
+
+    // Static keys are written together with their separators, in a single write
+    let obj = json_object! { a?: Some(1), "b c"?: Some(2), d?: missing, if true => e: 3 };
+    assert_eq!((7, r#"{"a":1,"b c":2,"e":3}"#.into()), count_writes(&obj));
+    let obj = json_object! { a?: missing, b?: Some(true) };
+    assert_eq!((3, r#"{"b":true}"#.into()), count_writes(&obj));
+}
+
+#[test]
+fn test_conditional_keys() {
+    let mut evaluated = false;
+    let mut expensive = || {
+        evaluated = true;
+        42
+    };
+    let obj = json_object! { if false => skipped: expensive(), kept: null, if true => ["x"]: 1 };
+    assert_eq!(r#"{"kept":null,"x":1}"#, obj.to_json_string());
+    assert!(!evaluated);
+    // The unconditional parts of the object stay zero-sized
+    let obj = json_object! { if 1 > 2 => a: 1u8, b: null, c: true };
+    assert_eq!(2, ::std::mem::size_of_val(&obj));
+}
+
+#[test]
+fn test_json_macro() {
+    assert_eq!("null", json!(null).to_json_string());
+    assert_eq!("[]", json!([]).to_json_string());
+    assert_eq!("{}", json!({}).to_json_string());
+    assert_eq!("[1,2]", json!([1, 1 + 1,]).to_json_string());
+    let x = 3;
+    let value = json!({
+        "a\"b\n": [true, false, null, [{}], {"x": x}],
+        "sum": x + 1,
+        "call": [x, 4].len(),
+        "block": { x * 2 }.to_string(),
+    });
+    let expected = r#"{"a\"b\n":[true,false,null,[{}],{"x":3}],"sum":4,"call":2,"block":"6"}"#;
+    assert_eq!(expected, value.to_json_string());
+    assert_eq!(Some(expected.len()), value.json_len());
+    assert_eq!(
+        "{\n  \"a\\\"b\\n\": [\n    true\n  ]\n}",
+        json!({"a\"b\n": [true]}).to_json_string_pretty()
+    );
+}
+
+#[test]
+fn test_literal_keys() {
+    let length = 12;
+    let encoding: Option<&str> = None;
+    let r#match = false;
+    let obj = json_object! {
+        "content-type": "text/plain",
+        "content-length": length,
+        "quote\"and\\slash": null,
+        "content-encoding"?: encoding,
+        if length > 10 => "x-large": true,
+        r#type: "response",
+        r#match,
+    };
+    let expected = r#"{"content-type":"text/plain","content-length":12,"quote\"and\\slash":null,"x-large":true,"type":"response","match":false}"#;
+    assert_eq!(expected, obj.to_json_string());
+    assert_eq!(Some(expected.len()), obj.json_len());
+    assert_eq!(
+        0,
+        std::mem::size_of_val(&json_object! {"a-b": true, r#type: null})
+    );
+    assert_eq!(
+        "{\n  \"a\\nb\": 1\n}",
+        json_object! {"a\nb": 1}.to_json_string_pretty()
+    );
+}
+
+/// Counts the calls to write_all
+#[derive(Default)]
+struct CountingWriter {
+    calls: usize,
+    bytes: Vec<u8>,
+}
+
+impl json_in_type::write::JSONWrite for CountingWriter {
+    type Error = json_in_type::error::JSONError;
+
+    fn write_all(&mut self, buf: &[u8]) -> Result<(), Self::Error> {
+        self.calls += 1;
+        self.bytes.extend_from_slice(buf);
+        Ok(())
+    }
+}
+
+fn count_writes<T: JSONValue>(value: &T) -> (usize, String) {
+    let mut w = CountingWriter::default();
+    value.write_json(&mut w).unwrap();
+    assert_eq!(Some(w.bytes.len()), value.json_len());
+    (w.calls, String::from_utf8(w.bytes).unwrap())
+}
+
+#[test]
+fn test_constant_folding() {
+    use json_in_type::constant::ConstJSONValue;
+
+    fn constant<T: ConstJSONValue>(value: &T) -> &'static str {
+        assert_eq!(T::JSON, value.to_json_string());
+        T::JSON
+    }
+
+    let obj = json_object! { a: null, b: true, "c\n": false };
+    assert_eq!(
+        (1, r#"{"a":null,"b":true,"c\n":false}"#.into()),
+        count_writes(&obj)
+    );
+    assert_eq!(r#"{"a":null,"b":true,"c\n":false}"#, constant(&obj));
+    assert_eq!(0, std::mem::size_of_val(&obj));
+
+    let x = 42;
+    let mixed = json_object! { a: null, b: true, x, c: false, d: null };
+    assert_eq!(
+        (4, r#"{"a":null,"b":true,"x":42,"c":false,"d":null}"#.into()),
+        count_writes(&mixed)
+    );
+
+    let list = json_list![null, true, x, false, null];
+    assert_eq!((4, "[null,true,42,false,null]".into()), count_writes(&list));
+    assert_eq!("[true,false]", constant(&json_list![true, false]));
+
+    let empty = json_object! { a: json_object!{}, "b": json_list![], c: null };
+    assert_eq!(r#"{"a":{},"b":[],"c":null}"#, constant(&empty));
+    assert_eq!(
+        "[{},[],true]",
+        constant(&json_list![json_object! {}, json_list![], true])
+    );
+    let after_value = json_object! { x, e: json_list![] };
+    assert_eq!((3, r#"{"x":42,"e":[]}"#.into()), count_writes(&after_value));
+    assert_eq!(
+        (4, "[[],42,{}]".into()),
+        count_writes(&json_list![json_list![], x, json_object! {}])
+    );
+    let delimiters = json_object! { a: json_object!(), "b": json_list!(), c: json_list! {} };
+    assert_eq!(r#"{"a":{},"b":[],"c":[]}"#, constant(&delimiters));
+    assert_eq!(
+        "[{},[],{}]",
+        constant(&json_list![json_object!(), json_list! {}, json_object![]])
+    );
+    let not_empty = json_object! { a: null, b: json_list!(x), c: json_object!() };
+    assert_eq!(
+        (6, r#"{"a":null,"b":[42],"c":{}}"#.into()),
+        count_writes(&not_empty)
+    );
+    assert_eq!(
+        (6, "[null,[42],[]]".into()),
+        count_writes(&json_list![null, json_list!(x), json_list!()])
+    );
+    let computed = json_object! { ["k".to_string()]: x, d: true };
+    assert_eq!(r#"{"k":42,"d":true}"#, computed.to_json_string());
+
+    let nested = json!({"a": [null, {"b": true, "c": []}], "x": x, "d": {"e": {}}});
+    assert_eq!(
+        (
+            4,
+            r#"{"a":[null,{"b":true,"c":[]}],"x":42,"d":{"e":{}}}"#.into()
+        ),
+        count_writes(&nested)
+    );
+    let nested_list = json!([[null], x, [{}, [true]]]);
+    assert_eq!(
+        (4, "[[null],42,[{},[true]]]".into()),
+        count_writes(&nested_list)
+    );
+    assert_eq!(
+        r#"{"a":[null,{"b":true,"c":[]}],"d":{"e":{}}}"#,
+        constant(&json!({"a": [null, {"b": true, "c": []}], "d": {"e": {}}}))
+    );
+    let partly_constant = json!({"a": [null, {"b": x}], "c": null});
+    assert_eq!(
+        (8, r#"{"a":[null,{"b":42}],"c":null}"#.into()),
+        count_writes(&partly_constant)
+    );
+}
+
+#[test]
+fn test_constant_folding_formatted() {
+    let obj = json!({"z": [null, {"b": true}], "a": {}, "m": 1});
+    assert_eq!(
+        "{\n  \"z\": [\n    null,\n    {\n      \"b\": true\n    }\n  ],\n  \"a\": {},\n  \"m\": 1\n}",
+        obj.to_json_string_pretty()
+    );
+    let mut canonical = Vec::new();
+    obj.write_json_formatted(&mut canonical, &mut format::JSONFormatter::canonical())
+        .unwrap();
+    assert_eq!(
+        br#"{"a":{},"m":1,"z":[null,{"b":true}]}"#.to_vec(),
+        canonical
+    );
+    assert_eq!(
+        "[\n  true,\n  1,\n  null\n]",
+        json_list![true, 1, null].to_json_string_pretty()
+    );
+}
+
+json_type! {
+    #[derive(Clone, Copy, Debug)]
+    struct Point {
+        x: i32,
+        "y-coord": i32,
+        r#type: &'static str,
+    }
+}
+
+json_type! {
+    struct Empty {}
+}
+
+struct Shape {
+    origin: Point,
+}
+
+#[test]
+fn test_json_type() {
+    fn origin() -> Point {
+        Point::from((0, -1, "origin"))
+    }
+    let shape = Shape { origin: origin() };
+    let copy = shape.origin;
+    let expected = r#"{"x":0,"y-coord":-1,"type":"origin"}"#;
+    assert_eq!(expected, copy.to_json_string());
+    assert_eq!(Some(expected.len()), shape.origin.json_len());
+    assert_eq!(
+        count_writes(&json_object! { x: 0, "y-coord": -1, r#type: "origin" }),
+        count_writes(&shape.origin),
+        "keys are written as in json_object!"
+    );
+    assert_eq!(
+        "{\n  \"x\": 0,\n  \"y-coord\": -1,\n  \"type\": \"origin\"\n}",
+        copy.to_json_string_pretty()
+    );
+    assert!(format!("{:?}", copy).contains(r#""y-coord""#));
+    assert_eq!(
+        std::mem::size_of_val(&json_object! { x: 0, "y-coord": -1, r#type: "origin" }),
+        std::mem::size_of::<Point>()
+    );
+    let extended = json_object! { ..copy, z: 2 };
+    assert_eq!(
+        r#"{"x":0,"y-coord":-1,"type":"origin","z":2}"#,
+        extended.to_json_string()
+    );
+    assert_eq!("{}", Empty::from(()).to_json_string());
+}
+
+#[test]
+fn test_derived_traits() {
+    use json_in_type::list::JSONList;
+    let x = 1;
+    let obj =
+        json_object! { a: x, "b": null, c: json_list![true, x], d?: Some(x), ..json_object! {} };
+    let copy = obj;
+    assert_eq!(obj.to_json_string(), copy.clone().to_json_string());
+    let debug = format!("{:?}", obj);
+    assert!(debug.contains(r#"key: "a", value: 1"#), "{}", debug);
+    let list = json_list![x].push(2).append(json_list![null]);
+    assert_eq!("[1,2,null]", list.clone().to_json_string());
+    assert!(format!("{:?}", list).starts_with("JSONListAppend"));
+}
+
+#[test]
+fn test_field_mut() {
+    use json_in_type::object::JSONObject;
+
+    let mut template = json_object! {
+        id: 0u32,
+        kind: null,
+        ok: true,
+        r#type: "a",
+        ..json_object! { extra: 1 },
+        "content-length"?: None,
+        [json_key!(computed)]: 0.5,
+    };
+    let mut output = vec![];
+    for id in 1..=3 {
+        *json_field_mut!(template, id) = id;
+        *json_field_mut!(template, "type") = if id % 2 == 0 { "even" } else { "odd" };
+        *json_field_mut!(template, "content-length") = Some(id * 10).filter(|&l| l > 10);
+        *json_field_mut!(template, computed) += 1.;
+        output.push(template.to_json_string());
+    }
+    assert_eq!(
+        vec![
+            r#"{"id":1,"kind":null,"ok":true,"type":"odd","extra":1,"computed":1.5}"#,
+            r#"{"id":2,"kind":null,"ok":true,"type":"even","extra":1,"content-length":20,"computed":2.5}"#,
+            r#"{"id":3,"kind":null,"ok":true,"type":"odd","extra":1,"content-length":30,"computed":3.5}"#,
+        ],
+        output
+    );
+
+    let mut built = json_object! { a: 1 }.with_static(json_key!(b), "x");
+    let spread = &mut built;
+    *json_field_mut!(*spread, b) = "y";
+    assert_eq!(r#"{"a":1,"b":"y"}"#, built.to_json_string());
+}